@@ -0,0 +1,382 @@
+use std::fs;
+use std::path::PathBuf;
+
+use multi_threaded_search::filter::{Level, LineFormat};
+use multi_threaded_search::fold::Folding;
+use multi_threaded_search::generate::{parse_size, Corpus, Sizes, Style};
+use multi_threaded_search::logger::Target;
+use multi_threaded_search::LOGS;
+
+pub const USAGE: &str = "\
+Usage: multi-threaded_search [--root <dir>]... [--threads <n>]
+       multi-threaded_search --query <search>... [--regex <pattern>]...
+                             [--match case|unicode|accents] [--index]
+                             [--lines] [--format text|json]
+                             [--level <level>] [--from <time>] [--to <time>]
+                             [--log-format common|json|<regex>] [--include <globs>]
+                             [--exclude <globs>] [--ignore-files]
+                             [--search-in contents|names|both] [--root <dir>]...
+       multi-threaded_search --generate <files> [--seed <n>] [--min-size <size>]
+                             [--max-size <size>] [--sizes uniform|long-tail]
+                             [--depth <n>] [--style <style>] [--root <dir>]
+
+Without --query or --regex the search window is opened. With them the files
+are searched without opening a window and the results are printed, exiting
+with 0 if anything matched, 1 if nothing did and 2 on an error. With
+--generate test files are written into the (first) root instead, the same
+seed and settings always giving the same files.
+
+Options:
+    --root <dir>        Directory to search, including its subdirectories.
+                        Can be given more than once. Defaults to ./logs
+    --threads <n>       Number of threads searching files.
+                        Defaults to the number of CPUs
+    --query <search>    Search using the same syntax as the search bars,
+                        e.g. \"timeout AND NOT retry\". Can be given more than
+                        once, like filling in more search bars
+    --regex <pattern>   Search for a regular expression, like a search bar
+                        with Regex ticked. Can be given more than once and
+                        along with --query, every one has to match
+    --match <how>       How plain terms are compared: case (the default)
+                        ignores case, unicode uses full Unicode case folding
+                        and accents also ignores accents, like Match in the
+                        window. Regexes always just ignore case
+    --index             Use the index in ./logs.index to skip files that
+                        can't match, like the window does, bringing it up to
+                        date (or building it) first
+    --lines             Print every matching line (up to 1000 per file)
+                        rather than the matching files
+    --format <format>   How results are printed, text (the default) or json
+    --level <level>     Only search lines logged at this level or above,
+                        e.g. error
+    --from <time>       Only search lines logged at or after this time,
+                        e.g. 02:00 or \"2024-01-02 02:00\"
+    --to <time>         Only search lines logged at or before this time
+    --log-format <f>    How the time and level are found in each line for
+                        the three above: common (the default) for lines like
+                        \"2024-01-02 02:03:04 ERROR message\", json for JSON lines,
+                        or a regex with (?P<time>...) and (?P<level>...) groups
+    --include <globs>   Only search the files matching one of these globs,
+                        separated by commas, e.g. \"*.log,payments/**\". A glob
+                        without a / is matched against the file's name and the
+                        names of the directories it is in
+    --exclude <globs>   Leave out the files and directories matching any of
+                        these globs, e.g. \"*debug*\"
+    --ignore-files      Leave out what .gitignore and .ignore files say to
+    --search-in <what>  Match the search against the file contents (the
+                        default), the file names or both
+    --generate <files>  Write this many test files to search
+    --seed <n>          What the files are made up from. Defaults to 1
+    --min-size <size>   Size of the smallest file, e.g. 500, 70kb or 2mb.
+                        Defaults to 10kb
+    --max-size <size>   Size of the biggest file. Defaults to 100kb
+    --sizes <spread>    uniform (the default) or long-tail for mostly small
+                        files and a few big ones
+    --depth <n>         How many directories deep the files are put.
+                        Defaults to 0, straight into the root
+    --style <style>     What the lines look like: prose (the default),
+                        syslog, json or stack-traces
+    -h, --help          Print this message";
+
+// What the app is started with, passed in through `Application::Flags`.
+#[derive(Debug, Clone, Default)]
+pub struct Flags {
+    pub roots: Vec<PathBuf>,
+    pub threads: usize,
+    // searches to run without a window, see `cli::run`
+    pub queries: Vec<String>,
+    pub regexes: Vec<String>,
+    pub folding: Folding,
+    pub index: bool,
+    pub lines: bool,
+    pub format: Format,
+    // only lines that pass these are searched, see `Filter`
+    pub log_format: LineFormat,
+    pub level: Option<Level>,
+    pub from: String,
+    pub to: String,
+    // which files are searched and what is matched in them, see `Selection` and `Target`
+    pub include: String,
+    pub exclude: String,
+    pub ignore_files: bool,
+    pub target: Target,
+    // test files to write without a window, see `cli::generate`
+    pub generate: Option<Corpus>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub enum ArgsError {
+    Help,
+    Invalid(String),
+}
+
+impl Flags {
+    // true when searching without a window rather than opening one
+    pub fn searches(&self) -> bool {
+        !self.queries.is_empty() || !self.regexes.is_empty()
+    }
+
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Flags, ArgsError> {
+        let mut roots = Vec::new();
+        let mut threads = None;
+        let mut queries = Vec::new();
+        let mut regexes = Vec::new();
+        let mut folding = None;
+        let mut index = false;
+        let mut lines = false;
+        let mut format = None;
+        let mut log_format = None;
+        let mut level = None;
+        let mut from = String::new();
+        let mut to = String::new();
+        let mut include = String::new();
+        let mut exclude = String::new();
+        let mut ignore_files = false;
+        let mut target = None;
+        let mut generate = None;
+        // the settings for --generate, only allowed along with it
+        let mut corpus = Corpus::default();
+        let mut corpus_flags = false;
+        while let Some(arg) = args.next() {
+            // `--flag=value` is the same as `--flag value`
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg, None),
+            };
+            let mut value = |what: &str| {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| ArgsError::Invalid(format!("{} needs {}", flag, what)))
+            };
+            match &flag[..] {
+                "-h" | "--help" => return Err(ArgsError::Help),
+                "--root" => roots.push(PathBuf::from(value("a directory")?)),
+                "--threads" => threads = Some(parse_threads(&value("a number")?)?),
+                "--query" => queries.push(value("a search")?),
+                "--regex" => regexes.push(value("a pattern")?),
+                "--match" => {
+                    folding = Some(match &value("case, unicode or accents")?[..] {
+                        "case" => Folding::Case,
+                        "unicode" => Folding::Full,
+                        "accents" => Folding::Accents,
+                        other => {
+                            return Err(ArgsError::Invalid(format!(
+                                "Unknown --match '{}', use case, unicode or accents",
+                                other
+                            )))
+                        }
+                    })
+                }
+                "--index" => index = true,
+                "--lines" => lines = true,
+                "--format" => {
+                    format = Some(match &value("a format")?[..] {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        other => return Err(ArgsError::Invalid(format!("Unknown format '{}', use text or json", other))),
+                    })
+                }
+                "--level" => {
+                    let name = value("a level")?;
+                    level = Some(Level::parse(&name).ok_or_else(|| {
+                        ArgsError::Invalid(format!("Unknown level '{}', use e.g. debug, info, warn or error", name))
+                    })?)
+                }
+                "--from" => from = value("a time")?,
+                "--to" => to = value("a time")?,
+                "--log-format" => {
+                    log_format = Some(match &value("a format")?[..] {
+                        "common" => LineFormat::Common,
+                        "json" => LineFormat::JsonLines,
+                        regex => LineFormat::Custom(regex.to_string()),
+                    })
+                }
+                "--include" => include = value("globs")?,
+                "--exclude" => exclude = value("globs")?,
+                "--ignore-files" => ignore_files = true,
+                "--search-in" => {
+                    target = Some(match &value("contents, names or both")?[..] {
+                        "contents" => Target::Contents,
+                        "names" => Target::Names,
+                        "both" => Target::Both,
+                        other => {
+                            return Err(ArgsError::Invalid(format!(
+                                "Unknown --search-in '{}', use contents, names or both",
+                                other
+                            )))
+                        }
+                    })
+                }
+                "--generate" => generate = Some(parse_number(&flag, &value("a number of files")?)?),
+                "--seed" => corpus.seed = parse_number(&flag, &value("a number")?)?,
+                "--min-size" => corpus.min_size = parse_size(&value("a size")?).map_err(ArgsError::Invalid)?,
+                "--max-size" => corpus.max_size = parse_size(&value("a size")?).map_err(ArgsError::Invalid)?,
+                "--sizes" => {
+                    let name = value("a spread")?;
+                    corpus.sizes = Sizes::parse(&name).ok_or_else(|| {
+                        ArgsError::Invalid(format!("Unknown size spread '{}', use uniform or long-tail", name))
+                    })?
+                }
+                "--depth" => corpus.depth = parse_number(&flag, &value("a number")?)?,
+                "--style" => {
+                    let name = value("a style")?;
+                    corpus.style = Style::parse(&name).ok_or_else(|| {
+                        ArgsError::Invalid(format!(
+                            "Unknown style '{}', use prose, syslog, json or stack-traces",
+                            name
+                        ))
+                    })?
+                }
+                _ => return Err(ArgsError::Invalid(format!("Unknown argument '{}'", flag))),
+            }
+            corpus_flags |= matches!(&flag[..], "--seed" | "--min-size" | "--max-size" | "--sizes" | "--depth" | "--style");
+        }
+        // --query and --regex are both search bars, either one searches without a window
+        let searching = !queries.is_empty() || !regexes.is_empty();
+        let filtered = level.is_some() || !from.is_empty() || !to.is_empty() || log_format.is_some();
+        if !searching && (lines || format.is_some() || filtered) {
+            return Err(ArgsError::Invalid(
+                "--lines, --format, --level, --from, --to and --log-format only work with --query or --regex"
+                    .to_string(),
+            ));
+        }
+        let selected = !include.is_empty() || !exclude.is_empty() || ignore_files || target.is_some();
+        if !searching && selected {
+            return Err(ArgsError::Invalid(
+                "--include, --exclude, --ignore-files and --search-in only work with --query or --regex".to_string(),
+            ));
+        }
+        if !searching && (folding.is_some() || index) {
+            return Err(ArgsError::Invalid("--match and --index only work with --query or --regex".to_string()));
+        }
+        if generate.is_none() && corpus_flags {
+            return Err(ArgsError::Invalid(
+                "--seed, --min-size, --max-size, --sizes, --depth and --style only work with --generate".to_string(),
+            ));
+        }
+        if generate.is_some() && searching {
+            return Err(ArgsError::Invalid("--generate can't be used with --query or --regex".to_string()));
+        }
+        let generate = generate.map(|files| Corpus { files, ..corpus });
+        if roots.is_empty() {
+            roots.push(PathBuf::from(LOGS));
+        }
+        // the file watcher reports absolute paths, so make the roots match,
+        // including those that don't exist yet and are waited for
+        let current = std::env::current_dir().unwrap_or_default();
+        let roots = roots
+            .into_iter()
+            .map(|root| fs::canonicalize(&root).unwrap_or_else(|_| current.join(root).components().collect()))
+            .collect();
+        let threads = threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(4, |n| n.get())
+        });
+        Ok(Flags {
+            roots,
+            threads,
+            queries,
+            regexes,
+            folding: folding.unwrap_or_default(),
+            index,
+            lines,
+            format: format.unwrap_or_default(),
+            log_format: log_format.unwrap_or_default(),
+            level,
+            from,
+            to,
+            include,
+            exclude,
+            ignore_files,
+            target: target.unwrap_or_default(),
+            generate,
+        })
+    }
+}
+
+fn parse_threads(n: &str) -> Result<usize, ArgsError> {
+    match n.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(ArgsError::Invalid(format!("--threads needs a number above 0, not '{}'", n))),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, n: &str) -> Result<T, ArgsError> {
+    n.parse()
+        .map_err(|_| ArgsError::Invalid(format!("{} needs a whole number, not '{}'", flag, n)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Flags, ArgsError> {
+        Flags::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    // the message of an invalid set of arguments
+    fn invalid(args: &[&str]) -> String {
+        match parse(args) {
+            Err(ArgsError::Invalid(message)) => message,
+            other => panic!("{:?} gave {:?}", args, other),
+        }
+    }
+
+    #[test]
+    fn searches_without_a_window() {
+        let flags = parse(&[
+            "--query", "timeout AND NOT retry", "--regex=err(or)?", "--match", "accents", "--lines",
+            "--format=json", "--level", "warning", "--from", "02:00", "--search-in", "both", "--threads", "3",
+        ])
+        .unwrap();
+        assert!(flags.searches());
+        assert_eq!(flags.queries, ["timeout AND NOT retry"]);
+        assert_eq!(flags.regexes, ["err(or)?"]);
+        assert_eq!((flags.folding, flags.format, flags.target), (Folding::Accents, Format::Json, Target::Both));
+        assert_eq!((flags.level, &flags.from[..], flags.threads), (Some(Level::Warn), "02:00", 3));
+        assert!(flags.lines && !flags.index);
+    }
+
+    #[test]
+    fn roots_are_absolute_whether_they_exist_or_not() {
+        let flags = parse(&["--root", "./not/there/../yet"]).unwrap();
+        assert!(!flags.searches());
+        let current = std::env::current_dir().unwrap();
+        assert_eq!(flags.roots, [current.join("not/there/../yet").components().collect::<PathBuf>()]);
+        assert!(flags.roots[0].is_absolute());
+        // the default
+        let flags = parse(&[]).unwrap();
+        assert!(flags.roots[0].is_absolute() && flags.roots[0].ends_with("logs"));
+    }
+
+    #[test]
+    fn generates_test_files() {
+        let flags = parse(&["--generate", "20", "--seed=7", "--max-size", "2mb", "--style", "json"]).unwrap();
+        let corpus = flags.generate.unwrap();
+        assert_eq!((corpus.files, corpus.seed, corpus.max_size), (20, 7, 2_000_000));
+        assert_eq!(corpus.style, Style::parse("json").unwrap());
+    }
+
+    #[test]
+    fn says_what_is_wrong() {
+        assert!(matches!(parse(&["--root", "logs", "-h"]), Err(ArgsError::Help)));
+        assert_eq!(invalid(&["--threads", "0"]), "--threads needs a number above 0, not '0'");
+        assert_eq!(invalid(&["--query"]), "--query needs a search");
+        assert_eq!(invalid(&["--frobnicate"]), "Unknown argument '--frobnicate'");
+        assert_eq!(invalid(&["--query", "x", "--match", "loose"]), "Unknown --match 'loose', use case, unicode or accents");
+        // options that only mean something along with others
+        assert!(invalid(&["--lines"]).ends_with("only work with --query or --regex"));
+        assert!(invalid(&["--include", "*.log"]).ends_with("only work with --query or --regex"));
+        assert!(invalid(&["--index"]).ends_with("only work with --query or --regex"));
+        assert!(invalid(&["--seed", "3"]).ends_with("only work with --generate"));
+        assert_eq!(invalid(&["--generate", "5", "--query", "x"]), "--generate can't be used with --query or --regex");
+        assert_eq!(invalid(&["--generate", "five"]), "--generate needs a whole number, not 'five'");
+    }
+}
@@ -0,0 +1,294 @@
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+use iced::futures::executor::block_on;
+use serde::Serialize;
+
+use multi_threaded_search::files::{list_files, Selection};
+use multi_threaded_search::filter::Filter;
+use multi_threaded_search::generate::Corpus;
+use multi_threaded_search::highlight::Snippet;
+use multi_threaded_search::index::Index;
+use multi_threaded_search::logger::{spawn_workers, Found, Mode, Search, Target};
+use multi_threaded_search::matcher::Hit;
+use multi_threaded_search::query::Query;
+use multi_threaded_search::stats::SearchStats;
+
+use crate::args::{Flags, Format};
+
+// Search without opening a window, for `--query`.
+// Returns the exit code: 0 if anything matched, 1 if nothing did and 2 on an error.
+pub fn run(flags: Flags) -> i32 {
+    let mut queries = Vec::with_capacity(flags.queries.len() + flags.regexes.len());
+    // blank ones are skipped like empty search bars, so `--query "" --level error` lists every error
+    for text in flags.queries.iter().filter(|text| !text.trim().is_empty()) {
+        match Query::parse(text) {
+            Ok(query) => queries.push(query),
+            Err(e) => {
+                eprintln!("Error in --query '{}': {}", text, e);
+                return 2;
+            }
+        }
+    }
+    for pattern in flags.regexes.iter().filter(|pattern| !pattern.trim().is_empty()) {
+        match Query::regex(pattern) {
+            Ok(query) => queries.push(query),
+            Err(e) => {
+                eprintln!("Error in --regex '{}': {}", pattern, e);
+                return 2;
+            }
+        }
+    }
+    let filter = match Filter::new(&flags.log_format, flags.level, &flags.from, &flags.to) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("Error in --from, --to or --log-format: {}", e);
+            return 2;
+        }
+    };
+    let selection = match Selection::new(&flags.include, &flags.exclude, flags.ignore_files) {
+        Ok(selection) => selection,
+        Err(e) => {
+            eprintln!("Error in --include or --exclude: {}", e);
+            return 2;
+        }
+    };
+    // a mistyped root would otherwise look like a search that found nothing
+    for root in &flags.roots {
+        if let Err(e) = fs::read_dir(root) {
+            eprintln!("Can't read --root {}: {}", root.display(), e);
+            return 2;
+        }
+    }
+    let mode = if flags.lines { Mode::Lines } else { Mode::Files };
+    // every --query and --regex is another AND, just like the search bars
+    let query = Query::And(queries).fold(flags.folding);
+    let mut files = list_files(&flags.roots, &selection);
+    // as in `State::search`, the index only knows about contents
+    if flags.index && (flags.target == Target::Contents || mode == Mode::Lines) {
+        let (index, status) = block_on(Index::open(flags.roots.clone()));
+        if flags.format == Format::Text {
+            eprintln!("{}", status.text());
+        }
+        files = index.narrow(&query, files);
+    }
+    let search = Search::new(0, query, mode, files)
+        .with_filter(filter)
+        .with_target(flags.target);
+    let mut stats = SearchStats::new();
+    let (sender, receiver) = mpsc::channel();
+    spawn_workers(&search, flags.threads, move |_, found, file_stats| {
+        sender.send((found, file_stats)).is_ok()
+    });
+    // the loop ends once the workers are done and have dropped every sender
+    let mut results = Vec::new();
+    let mut skipped = Vec::new();
+    for (found, file_stats) in receiver {
+        stats.add(&file_stats);
+        match found {
+            Some(Found::Skipped(file)) => skipped.push(Skip {
+                path: file.file.path().to_string_lossy().to_string(),
+                reason: file.error.to_string(),
+            }),
+            found => results.extend(found),
+        }
+    }
+    stats.finish(0);
+    let mut output = results.into_iter().flat_map(Output::from_found).collect::<Vec<_>>();
+    // the workers finish in any order, sort so the same files always print the same
+    output.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    skipped.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let written = match flags.format {
+        Format::Text => write_text(&mut stdout, &output),
+        Format::Json => {
+            let json = Json {
+                results: &output,
+                skipped: &skipped,
+                stats: stats.fields().into_iter().collect(),
+            };
+            serde_json::to_writer_pretty(&mut stdout, &json)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(stdout))
+        }
+    };
+    if flags.format == Format::Text {
+        for file in &skipped {
+            eprintln!("Skipped {}: {}", file.path, file.reason);
+        }
+        let stats: Vec<String> = stats
+            .fields()
+            .into_iter()
+            .map(|(label, value)| format!("{}: {}", label, value))
+            .collect();
+        eprintln!("{}", stats.join(", "));
+    }
+    match written {
+        // stdout was closed early, e.g. piped into `head`
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            eprintln!("Error writing results: {}", e);
+            2
+        }
+        _ if output.is_empty() => 1,
+        _ => 0,
+    }
+}
+
+// Write test files into the first root for `--generate`, one thread per `--threads`.
+// Returns the exit code: 0 once every file is written, 2 if any couldn't be.
+pub fn generate(flags: &Flags, corpus: &Corpus) -> i32 {
+    let root = &flags.roots[0];
+    let start = Instant::now();
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let (mut written, mut bytes, mut failed) = (0, 0, 0);
+    // the running count is only for someone watching, not for a log file
+    let progress = io::stderr().is_terminal();
+    thread::scope(|scope| {
+        for _ in 0..flags.threads.min(corpus.files.max(1)) {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= corpus.files {
+                    break;
+                }
+                if sender.send((index, corpus.write(root, index))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        for (index, result) in receiver {
+            match result {
+                Ok(size) => {
+                    written += 1;
+                    bytes += size;
+                }
+                Err(e) => {
+                    failed += 1;
+                    eprintln!("\rCouldn't write {}: {}", root.join(corpus.path(index)).display(), e);
+                }
+            }
+            if progress {
+                eprint!("\rCreated {} of {}", written, corpus.files);
+            }
+        }
+    });
+    eprintln!(
+        "\rCreated {} files, {:.1}MB in {} in {:.2}s (seed {})",
+        written,
+        bytes as f64 / 1_000_000.0,
+        root.display(),
+        start.elapsed().as_secs_f64(),
+        corpus.seed,
+    );
+    if failed > 0 {
+        2
+    } else {
+        0
+    }
+}
+
+#[derive(Serialize)]
+struct Json<'a> {
+    results: &'a [Output],
+    skipped: &'a [Skip],
+    stats: std::collections::BTreeMap<&'static str, String>,
+}
+
+// One matching file, or one matching line with `--lines`.
+#[derive(Serialize)]
+struct Output {
+    path: String,
+    // only for `--lines`, where each line is its own result
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    // byte ranges of `text` that matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matches: Option<Vec<(usize, usize)>>,
+    // only for matching files
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hits: Vec<Hit>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    snippets: Vec<Line>,
+}
+
+// a file that couldn't be searched and why, unreadable and binary files
+#[derive(Serialize)]
+struct Skip {
+    path: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct Line {
+    line: usize,
+    text: String,
+    matches: Vec<(usize, usize)>,
+}
+
+impl Output {
+    fn from_found(found: Found) -> Vec<Output> {
+        match found {
+            Found::File(found) => vec![Output {
+                path: found.file.path().to_string_lossy().to_string(),
+                line: None,
+                text: None,
+                matches: None,
+                hits: found.hits.counts().to_vec(),
+                snippets: found.snippets.iter().map(Line::from_snippet).collect(),
+            }],
+            Found::Lines(lines) => lines
+                .into_iter()
+                .map(|found| {
+                    let line = Line::from_snippet(&found.snippet);
+                    Output {
+                        path: found.file.path().to_string_lossy().to_string(),
+                        line: Some(line.line),
+                        text: Some(line.text),
+                        matches: Some(line.matches),
+                        hits: Vec::new(),
+                        snippets: Vec::new(),
+                    }
+                })
+                .collect(),
+            // listed on their own, see `Skip`
+            Found::Skipped(_) => Vec::new(),
+        }
+    }
+}
+
+impl Line {
+    fn from_snippet(snippet: &Snippet) -> Line {
+        Line {
+            line: snippet.line_number,
+            text: snippet.text.clone(),
+            matches: snippet.highlights.iter().map(|range| (range.start, range.end)).collect(),
+        }
+    }
+}
+
+// grep style, `path:line: text` for lines and the path followed by its snippets for files
+fn write_text(out: &mut impl Write, output: &[Output]) -> io::Result<()> {
+    for result in output {
+        match (&result.line, &result.text) {
+            (Some(line), Some(text)) => writeln!(out, "{}:{}: {}", result.path, line, text)?,
+            _ => {
+                let hits: Vec<String> = result.hits.iter().map(Hit::to_string).collect();
+                writeln!(out, "{}  {}", result.path, hits.join("  "))?;
+                for snippet in &result.snippets {
+                    writeln!(out, "    {}: {}", snippet.line, snippet.text)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
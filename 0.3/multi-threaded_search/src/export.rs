@@ -0,0 +1,201 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use serde::Serialize;
+
+use multi_threaded_search::highlight::Snippet;
+use multi_threaded_search::logger::LineMatch;
+use multi_threaded_search::matcher::{Hit, Hits};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Json, ExportFormat::Csv, ExportFormat::Markdown];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Markdown => "md",
+        }
+    }
+
+    // Swap the extension of `path` for this format's, unless it was given one of its own.
+    pub fn with_extension(self, path: &str) -> String {
+        let known = ExportFormat::ALL.iter().map(|format| format!(".{}", format.extension()));
+        for extension in known {
+            if let Some(stem) = path.strip_suffix(&extension[..]) {
+                return format!("{}.{}", stem, self.extension());
+            }
+        }
+        path.to_string()
+    }
+}
+
+// what the format picker shows
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExportFormat::Json => "JSON",
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Markdown => "Markdown table",
+        })
+    }
+}
+
+// One file of the results with what matched in it. In `Mode::Files` the lines
+// are the snippets shown under the file, in `Mode::Lines` every line that matched.
+#[derive(Debug, Clone)]
+pub struct Exported {
+    pub path: PathBuf,
+    pub hits: Hits,
+    pub matches: usize,
+    pub lines: Vec<Snippet>,
+}
+
+impl Exported {
+    // The matching lines grouped by the file they are from, in the order they were found.
+    pub fn from_lines(lines: &[LineMatch]) -> Vec<Exported> {
+        let mut exported: Vec<Exported> = Vec::new();
+        for line in lines {
+            let path = line.file.path();
+            match exported.iter_mut().find(|file| file.path == path) {
+                Some(file) => {
+                    file.matches += 1;
+                    file.lines.push(line.snippet.clone());
+                }
+                None => exported.push(Exported {
+                    path,
+                    hits: Hits::default(),
+                    matches: 1,
+                    lines: vec![line.snippet.clone()],
+                }),
+            }
+        }
+        exported
+    }
+}
+
+// What gets written, with the size and modified time read from the file as it is now.
+#[derive(Serialize)]
+struct Record {
+    path: String,
+    size: Option<u64>,
+    modified: Option<String>,
+    matches: usize,
+    hits: Vec<Hit>,
+    lines: Vec<Line>,
+}
+
+#[derive(Serialize)]
+struct Line {
+    line: usize,
+    text: String,
+}
+
+impl Record {
+    fn new(file: Exported) -> Record {
+        let metadata = fs::metadata(&file.path).ok();
+        Record {
+            path: file.path.to_string_lossy().to_string(),
+            size: metadata.as_ref().map(|metadata| metadata.len()),
+            modified: metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .map(|time| humantime::format_rfc3339_seconds(time).to_string()),
+            matches: file.matches,
+            hits: file.hits.counts().to_vec(),
+            lines: file
+                .lines
+                .into_iter()
+                .map(|snippet| Line {
+                    line: snippet.line_number,
+                    text: snippet.text,
+                })
+                .collect(),
+        }
+    }
+
+    fn hits_text(&self) -> String {
+        let hits: Vec<String> = self.hits.iter().map(Hit::to_string).collect();
+        hits.join("; ")
+    }
+}
+
+// Write the results to `path`, returning what to tell the user either way.
+pub async fn export(path: PathBuf, format: ExportFormat, files: Vec<Exported>) -> Result<String, String> {
+    let count = files.len();
+    let records: Vec<Record> = files.into_iter().map(Record::new).collect();
+    let written = fs::File::create(&path).and_then(|file| {
+        let mut out = io::BufWriter::new(file);
+        match format {
+            ExportFormat::Json => serde_json::to_writer_pretty(&mut out, &records)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(out)),
+            ExportFormat::Csv => write_csv(&mut out, &records),
+            ExportFormat::Markdown => write_markdown(&mut out, &records),
+        }?;
+        out.flush()
+    });
+    match written {
+        Ok(()) => Ok(format!("Exported {} files to {}", count, path.display())),
+        Err(e) => Err(format!("Unable to export to {}: {}", path.display(), e)),
+    }
+}
+
+// One row per line so it can be filtered in a spreadsheet, the file's columns repeated on each.
+fn write_csv(out: &mut impl Write, records: &[Record]) -> io::Result<()> {
+    let mut csv = csv::Writer::from_writer(out);
+    csv.write_record(["path", "size", "modified", "matches", "hits", "line", "text"])?;
+    for record in records {
+        let file = [
+            record.path.clone(),
+            record.size.map_or_else(String::new, |size| size.to_string()),
+            record.modified.clone().unwrap_or_default(),
+            record.matches.to_string(),
+            record.hits_text(),
+        ];
+        if record.lines.is_empty() {
+            csv.write_record(file.iter().map(String::as_str).chain(["", ""]))?;
+        }
+        for line in &record.lines {
+            let line_number = line.line.to_string();
+            csv.write_record(file.iter().map(String::as_str).chain([&line_number[..], &line.text[..]]))?;
+        }
+    }
+    csv.flush()
+}
+
+// One row per file with its lines in the last cell, ready to paste into a report.
+fn write_markdown(out: &mut impl Write, records: &[Record]) -> io::Result<()> {
+    writeln!(out, "| File | Size | Modified | Matches | Hits | Lines |")?;
+    writeln!(out, "| --- | ---: | --- | ---: | --- | --- |")?;
+    for record in records {
+        let lines: Vec<String> = record
+            .lines
+            .iter()
+            .map(|line| format!("{}: `{}`", line.line, line.text.replace('`', "'")))
+            .collect();
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} |",
+            markdown_cell(&record.path),
+            record.size.map_or_else(String::new, |size| size.to_string()),
+            record.modified.as_deref().unwrap_or(""),
+            record.matches,
+            markdown_cell(&record.hits_text()),
+            markdown_cell(&lines.join("<br>")),
+        )?;
+    }
+    Ok(())
+}
+
+// a `|` would end the cell early
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
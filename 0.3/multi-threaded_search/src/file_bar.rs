@@ -0,0 +1,103 @@
+use iced::{text_input, Checkbox, Column, Element, Length, Radio, Row, Text, TextInput};
+use serde::{Deserialize, Serialize};
+
+use multi_threaded_search::files::Selection;
+use multi_threaded_search::logger::Target;
+
+use crate::ERROR_COLOR;
+
+// The rows under the filter for picking which files are searched, and whether it is
+// their names or their contents that are. Like `FilterBar` it is turned into part of
+// the `Search` when one starts, see `State::search`.
+#[derive(Debug, Clone, Default)]
+pub struct FileBar {
+    include: String,
+    exclude: String,
+    ignore_files: bool,
+    pub target: Target,
+    pub error: Option<String>,
+    include_state: text_input::State,
+    exclude_state: text_input::State,
+}
+
+#[derive(Debug, Clone)]
+pub enum FileMessage {
+    IncludeChanged(String),
+    ExcludeChanged(String),
+    SetIgnoreFiles(bool),
+    SetTarget(Target),
+}
+
+// The files picked as they are kept with a saved search.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedFiles {
+    pub include: String,
+    pub exclude: String,
+    pub ignore_files: bool,
+    pub target: Target,
+}
+
+impl FileBar {
+    pub fn update(&mut self, message: FileMessage) {
+        match message {
+            FileMessage::IncludeChanged(include) => self.include = include,
+            FileMessage::ExcludeChanged(exclude) => self.exclude = exclude,
+            FileMessage::SetIgnoreFiles(ignore_files) => self.ignore_files = ignore_files,
+            FileMessage::SetTarget(target) => self.target = target,
+        }
+    }
+
+    pub fn selection(&self) -> Result<Selection, String> {
+        Selection::new(&self.include, &self.exclude, self.ignore_files)
+    }
+
+    pub fn saved(&self) -> SavedFiles {
+        SavedFiles {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            ignore_files: self.ignore_files,
+            target: self.target,
+        }
+    }
+
+    pub fn restore(&mut self, saved: SavedFiles) {
+        self.include = saved.include;
+        self.exclude = saved.exclude;
+        self.ignore_files = saved.ignore_files;
+        self.target = saved.target;
+    }
+
+    pub fn unfocus(&mut self) {
+        self.include_state.unfocus();
+        self.exclude_state.unfocus();
+    }
+
+    pub fn view(&mut self) -> Element<'_, FileMessage> {
+        let globs = Row::new()
+            .spacing(10)
+            .push(Text::new("Files:"))
+            .push(
+                TextInput::new(&mut self.include_state, "*.log, payments/**", &self.include, FileMessage::IncludeChanged)
+                    .width(Length::Units(200))
+                    .padding(5),
+            )
+            .push(Text::new("except"))
+            .push(
+                TextInput::new(&mut self.exclude_state, "*debug*", &self.exclude, FileMessage::ExcludeChanged)
+                    .width(Length::Units(200))
+                    .padding(5),
+            )
+            .push(Checkbox::new(self.ignore_files, "Use .gitignore", FileMessage::SetIgnoreFiles));
+        let target = Row::new()
+            .spacing(20)
+            .push(Radio::new(Target::Contents, "In contents", Some(self.target), FileMessage::SetTarget))
+            .push(Radio::new(Target::Names, "In file names", Some(self.target), FileMessage::SetTarget))
+            .push(Radio::new(Target::Both, "In both", Some(self.target), FileMessage::SetTarget));
+        let column = Column::new().spacing(10).push(globs).push(target);
+        match &self.error {
+            Some(error) => column.push(Text::new(error).size(16).color(ERROR_COLOR)).into(),
+            None => column.into(),
+        }
+    }
+}
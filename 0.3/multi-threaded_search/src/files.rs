@@ -0,0 +1,334 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+use crate::scan::Compression;
+
+// A file found under one of the search roots.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LogFile {
+    pub root: PathBuf,
+    // relative to `root`, this is what is shown in the results
+    pub relative: PathBuf,
+}
+
+impl LogFile {
+    pub fn path(&self) -> PathBuf {
+        self.root.join(&self.relative)
+    }
+
+    pub fn title(&self) -> String {
+        self.relative.to_string_lossy().to_string()
+    }
+
+    // find the root a path reported by the watcher belongs to
+    pub fn from_path(roots: &[PathBuf], path: &Path) -> Option<LogFile> {
+        roots.iter().find_map(|root| {
+            path.strip_prefix(root).ok().map(|relative| LogFile {
+                root: root.clone(),
+                relative: relative.to_path_buf(),
+            })
+        })
+    }
+}
+
+// What is shown about a file besides what it matched. For the files a search finds
+// it is read on the search thread along with the search, so the UI never has to.
+#[derive(Debug, Clone, Default)]
+pub struct FileInfo {
+    pub modified: Option<SystemTime>,
+    pub size: u64,
+    // how the file is compressed and how big it is decompressed, if it is
+    pub compressed: Option<(Compression, Option<u64>)>,
+}
+
+impl FileInfo {
+    // whatever can't be read is left out
+    pub fn read(path: &Path) -> FileInfo {
+        let metadata = fs::metadata(path).ok();
+        FileInfo {
+            modified: metadata.as_ref().and_then(|metadata| metadata.modified().ok()),
+            size: metadata.map_or(0, |metadata| metadata.len()),
+            compressed: Compression::of(path).map(|compression| (compression, compression.uncompressed_size(path))),
+        }
+    }
+}
+
+// Which of the files under the roots are searched, by glob and by the `.gitignore`
+// and `.ignore` files next to them. Excluded directories aren't gone into at all,
+// so a directory of huge dumps costs nothing to skip.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    // nothing included means everything is
+    include: Option<Globs>,
+    exclude: Option<Globs>,
+    ignore_files: bool,
+}
+
+// A list of globs, split by whether they are for a whole path or a single name.
+#[derive(Debug, Clone)]
+struct Globs {
+    // the ones with a `/`, matched against the path under the root, `*` doesn't cross a `/`
+    paths: GlobSet,
+    // the ones without, matched against the file's name and the directories it's in
+    names: GlobSet,
+}
+
+impl Selection {
+    // `include` and `exclude` are globs separated by commas or spaces, e.g. `*.log, payments/**`.
+    // A glob starting with `!` is excluded whichever list it is in.
+    pub fn new(include: &str, exclude: &str, ignore_files: bool) -> Result<Selection, String> {
+        let mut includes = Vec::new();
+        let mut excludes = Vec::new();
+        for (text, excluded) in [(include, false), (exclude, true)] {
+            for glob in text.split(|c: char| c == ',' || c.is_whitespace()).filter(|glob| !glob.is_empty()) {
+                match glob.strip_prefix('!') {
+                    Some(glob) => excludes.push(glob),
+                    None if excluded => excludes.push(glob),
+                    None => includes.push(glob),
+                }
+            }
+        }
+        Ok(Selection {
+            include: Globs::new(&includes)?,
+            exclude: Globs::new(&excludes)?,
+            ignore_files,
+        })
+    }
+
+    // whether a directory is gone into, before looking at any ignore files
+    fn enters(&self, relative: &Path) -> bool {
+        !self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(relative))
+    }
+
+    fn includes(&self, relative: &Path) -> bool {
+        self.include.as_ref().is_none_or(|include| include.is_match(relative))
+            && !self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(relative))
+    }
+
+    // the rules of the ignore files in `dir`, if they are being used and it has any
+    fn ignores_in(&self, dir: &Path) -> Option<Gitignore> {
+        if !self.ignore_files {
+            return None;
+        }
+        let mut builder = GitignoreBuilder::new(dir);
+        // `.ignore` comes last so it wins, as it does for ripgrep. Rules that don't
+        // parse are left out rather than ignoring nothing at all.
+        for name in [".gitignore", ".ignore"] {
+            let path = dir.join(name);
+            if path.is_file() {
+                builder.add(path);
+            }
+        }
+        builder.build().ok().filter(|ignores| !ignores.is_empty())
+    }
+}
+
+impl Globs {
+    fn new(globs: &[&str]) -> Result<Option<Globs>, String> {
+        if globs.is_empty() {
+            return Ok(None);
+        }
+        let mut paths = GlobSetBuilder::new();
+        let mut names = GlobSetBuilder::new();
+        for glob in globs {
+            let (set, text) = match glob.strip_prefix('/') {
+                // anchored to the root, like in a .gitignore
+                Some(text) => (&mut paths, text),
+                None if glob.contains('/') => (&mut paths, *glob),
+                None => (&mut names, *glob),
+            };
+            let glob = GlobBuilder::new(text)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("'{}' isn't a glob: {}", glob, e.kind()))?;
+            set.add(glob);
+        }
+        let build = |set: GlobSetBuilder| set.build().expect("every glob has been built on its own");
+        Ok(Some(Globs {
+            paths: build(paths),
+            names: build(names),
+        }))
+    }
+
+    fn is_match(&self, relative: &Path) -> bool {
+        self.paths.is_match(relative) || relative.iter().any(|name| self.names.is_match(name))
+    }
+}
+
+// Whether the ignore files, deepest first, have anything to say about `path`.
+fn ignored(ignores: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    for ignores in ignores.iter().rev() {
+        match ignores.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            // a `!` rule takes back what the ones above it ignored
+            Match::Whitelist(_) => return false,
+            Match::None => (),
+        }
+    }
+    false
+}
+
+// every file under `roots` that `selection` lets through, including the ones in subdirectories
+pub fn list_files(roots: &[PathBuf], selection: &Selection) -> Vec<LogFile> {
+    let mut files = Vec::new();
+    for root in roots {
+        let mut ignores: Vec<_> = selection.ignores_in(root).into_iter().collect();
+        visit(root, Path::new(""), selection, &mut ignores, &mut files);
+    }
+    files
+}
+
+// `path` itself if it is a file, or every file under it if it is a directory,
+// as long as `selection` lets them through
+pub fn files_at(roots: &[PathBuf], path: &Path, selection: &Selection) -> Vec<LogFile> {
+    let file = match LogFile::from_path(roots, path) {
+        Some(file) => file,
+        None => return Vec::new(),
+    };
+    // the ignore files on the way down to it, and none of the directories on the way can be left out
+    let mut ignores: Vec<_> = selection.ignores_in(&file.root).into_iter().collect();
+    let mut dir = PathBuf::new();
+    let parents: Vec<_> = file.relative.parent().map_or(Vec::new(), |parent| parent.iter().collect());
+    for name in parents {
+        dir.push(name);
+        let full = file.root.join(&dir);
+        if !selection.enters(&dir) || ignored(&ignores, &full, true) {
+            return Vec::new();
+        }
+        ignores.extend(selection.ignores_in(&full));
+    }
+    if path.is_dir() {
+        // the root itself has had its ignore files read already
+        let mut files = Vec::new();
+        if file.relative.as_os_str().is_empty() {
+            visit(&file.root, &file.relative, selection, &mut ignores, &mut files);
+        } else if selection.enters(&file.relative) && !ignored(&ignores, path, true) {
+            ignores.extend(selection.ignores_in(path));
+            visit(&file.root, &file.relative, selection, &mut ignores, &mut files);
+        }
+        files
+    } else if path.is_file() && selection.includes(&file.relative) && !ignored(&ignores, path, false) {
+        vec![file]
+    } else {
+        Vec::new()
+    }
+}
+
+// `ignores` has the rules of the ignore files in `dir` and the directories above it
+fn visit(root: &Path, dir: &Path, selection: &Selection, ignores: &mut Vec<Gitignore>, files: &mut Vec<LogFile>) {
+    // unreadable directories are skipped rather than stopping the whole listing
+    let entries = match fs::read_dir(root.join(dir)) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let relative = dir.join(entry.file_name());
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                if !selection.enters(&relative) || ignored(ignores, &path, true) {
+                    continue;
+                }
+                // the rules of the ignore files in there only hold under it
+                let depth = ignores.len();
+                ignores.extend(selection.ignores_in(&path));
+                visit(root, &relative, selection, ignores, files);
+                ignores.truncate(depth);
+            }
+            // symlinked files are searched, symlinked directories are not followed
+            // so a link back up the tree can't send us round in circles
+            Ok(file_type) if file_type.is_file() || (file_type.is_symlink() && path.is_file()) => {
+                if !selection.includes(&relative) || ignored(ignores, &path, false) {
+                    continue;
+                }
+                files.push(LogFile {
+                    root: root.to_path_buf(),
+                    relative,
+                })
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn selection(include: &str, exclude: &str) -> Selection {
+        Selection::new(include, exclude, false).unwrap()
+    }
+
+    // the files listed under `root`, as sorted paths under it
+    fn listed(root: &Path, selection: &Selection) -> Vec<String> {
+        let mut files: Vec<String> = list_files(&[root.to_path_buf()], selection)
+            .into_iter()
+            .map(|file| file.title().replace('\\', "/"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn globs_with_a_slash_match_the_path_others_any_name() {
+        let includes = |selection: &Selection, path: &str| selection.includes(Path::new(path));
+        let logs = selection("*.log", "");
+        assert!(includes(&logs, "app.log"));
+        assert!(includes(&logs, "payments/app.log"));
+        assert!(!includes(&logs, "app.log.1"));
+        let payments = selection("payments", "");
+        assert!(includes(&payments, "eu/payments/app.log"));
+        assert!(!includes(&payments, "eu/app-payments.log"));
+        // `*` stays within a directory, `**` goes into any number of them
+        assert!(includes(&selection("payments/*.log", ""), "payments/app.log"));
+        assert!(!includes(&selection("payments/*.log", ""), "payments/eu/app.log"));
+        assert!(includes(&selection("payments/**", ""), "payments/eu/app.log"));
+        assert!(!includes(&selection("/app.log", ""), "payments/app.log"));
+    }
+
+    #[test]
+    fn excludes_win_wherever_they_are_written() {
+        let includes = |selection: &Selection, path: &str| selection.includes(Path::new(path));
+        assert!(!includes(&selection("*.log", "*debug*"), "app-debug.log"));
+        assert!(!includes(&selection("*.log, !*debug*", ""), "app-debug.log"));
+        assert!(includes(&selection("*.log !*debug*", ""), "app.log"));
+        assert!(!selection("", "archive").enters(Path::new("archive")));
+        assert_eq!(
+            Selection::new("[a", "", false).unwrap_err(),
+            "'[a' isn't a glob: unclosed character class; missing ']'"
+        );
+    }
+
+    #[test]
+    fn ignore_files_leave_out_what_they_say() {
+        let root = env::temp_dir().join(format!("multi-threaded_search-ignore-{}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("old")).unwrap();
+        fs::create_dir_all(root.join("new")).unwrap();
+        fs::write(root.join(".gitignore"), "old/\n*.tmp\n").unwrap();
+        // `.ignore` wins over `.gitignore`
+        fs::write(root.join("new/.ignore"), "!keep.tmp\n").unwrap();
+        for file in ["app.log", "scratch.tmp", "old/app.log", "new/app.log", "new/keep.tmp"] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+        assert_eq!(
+            listed(&root, &Selection::default()),
+            [".gitignore", "app.log", "new/.ignore", "new/app.log", "new/keep.tmp", "old/app.log", "scratch.tmp"]
+        );
+        let selection = Selection::new("", "", true).unwrap();
+        assert_eq!(
+            listed(&root, &selection),
+            [".gitignore", "app.log", "new/.ignore", "new/app.log", "new/keep.tmp"]
+        );
+        // a single changed file is picked the same way as when listing them all
+        assert!(files_at(std::slice::from_ref(&root), &root.join("old/app.log"), &selection).is_empty());
+        assert_eq!(files_at(std::slice::from_ref(&root), &root.join("new/keep.tmp"), &selection).len(), 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,366 @@
+use std::cmp::Ordering;
+use std::fmt;
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+// Narrowing a search down to the lines logged at some level or within some time,
+// on top of what the query matches:
+//
+//   ERROR lines between 02:00 and 02:15 containing timeout
+//
+// Each line is parsed with a `LineParser` for its time and level, lines that
+// don't pass are skipped as if they weren't in the file at all.
+
+// How the lines of a file are laid out, so the time and level can be found in them.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LineFormat {
+    // `2024-01-02 02:03:04,567 [main] ERROR message` and the usual variations of it
+    #[default]
+    Common,
+    // one JSON object per line, with the time and level under their usual keys
+    JsonLines,
+    // a regex with `time` and/or `level` named groups
+    Custom(String),
+}
+
+// The usual log levels, least important first so they can be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Level {
+    pub const ALL: [Level; 6] = [Level::Trace, Level::Debug, Level::Info, Level::Warn, Level::Error, Level::Fatal];
+
+    // the names different loggers use for each level in any case, and their first letters
+    pub fn parse(name: &str) -> Option<Level> {
+        match &name.trim().to_lowercase()[..] {
+            "trace" | "t" | "v" | "finest" | "verbose" => Some(Level::Trace),
+            "debug" | "d" | "fine" | "finer" => Some(Level::Debug),
+            "info" | "i" | "information" | "notice" => Some(Level::Info),
+            "warn" | "w" | "warning" => Some(Level::Warn),
+            "error" | "e" | "err" | "severe" => Some(Level::Error),
+            "fatal" | "f" | "critical" | "crit" | "alert" | "emerg" | "panic" => Some(Level::Fatal),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+            Level::Fatal => "FATAL",
+        })
+    }
+}
+
+// When a line was logged, as written in it. Time zones are ignored, which is
+// right as long as the files being searched were all written in the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    // year, month and day, not every format has them
+    pub date: Option<(i64, u32, u32)>,
+    // seconds since midnight
+    pub seconds: u32,
+}
+
+impl Timestamp {
+    // `02:15`, `02:15:30`, `2024-01-02 02:15:30.123`, `2024-01-02T02:15:30Z` or `Jan  2 02:15:30`.
+    // A date on its own is midnight.
+    pub fn parse(text: &str) -> Option<Timestamp> {
+        Timestamp::parse_bound(text, false).map(|(timestamp, _)| timestamp)
+    }
+
+    // Like `parse`, also returning how many seconds the text leaves unsaid:
+    // `02:15` could be any second of that minute, `2024-01-02` any second of the day.
+    fn parse_bound(text: &str, end: bool) -> Option<(Timestamp, u32)> {
+        let text = text.trim();
+        let (date, time) = match text.get(..10).and_then(parse_date) {
+            Some(date) => (Some(date), text[10..].trim_start_matches(['T', ' '])),
+            // syslog style, a month and day without a year which is no use for comparing
+            None if text.len() > 6 && text.as_bytes()[0].is_ascii_uppercase() => {
+                (None, text.get(3..)?.trim_start().trim_start_matches(|c: char| c.is_ascii_digit()).trim_start())
+            }
+            None => (None, text),
+        };
+        if time.is_empty() {
+            return date.map(|date| (Timestamp { date: Some(date), seconds: 0 }, if end { 86_399 } else { 0 }));
+        }
+        let mut parts = time.splitn(3, ':');
+        let hours: u32 = parts.next()?.parse().ok()?;
+        let minutes: u32 = parts.next()?.get(..2)?.parse().ok()?;
+        let (seconds, unsaid) = match parts.next() {
+            Some(rest) => (rest.get(..2)?.parse().ok()?, 0),
+            None => (0, 59),
+        };
+        if hours > 23 || minutes > 59 || seconds > 60 {
+            return None;
+        }
+        let timestamp = Timestamp {
+            date,
+            seconds: hours * 3600 + minutes * 60 + seconds,
+        };
+        Some((timestamp, if end { unsaid } else { 0 }))
+    }
+
+    // Seconds or milliseconds since 1970, as JSON loggers often write them.
+    fn from_epoch(epoch: f64) -> Timestamp {
+        // anything this big can't be seconds, it would be tens of thousands of years away
+        let seconds = if epoch > 1e11 { epoch / 1000.0 } else { epoch } as i64;
+        let (days, seconds) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+        Timestamp {
+            date: Some(civil_from_days(days)),
+            seconds: seconds as u32,
+        }
+    }
+
+    // Times without a date, or bounds without one, are only compared by the time of day.
+    fn compare(&self, other: &Timestamp) -> Ordering {
+        match (self.date, other.date) {
+            (Some(a), Some(b)) => (a, self.seconds).cmp(&(b, other.seconds)),
+            _ => self.seconds.cmp(&other.seconds),
+        }
+    }
+}
+
+// `YYYY-MM-DD`
+fn parse_date(text: &str) -> Option<(i64, u32, u32)> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    let date = (text[..4].parse().ok()?, text[5..7].parse().ok()?, text[8..].parse().ok()?);
+    if (1..=12).contains(&date.1) && (1..=31).contains(&date.2) {
+        Some(date)
+    } else {
+        None
+    }
+}
+
+// The year, month and day `days` after 1970-01-01, from Howard Hinnant's `civil_from_days`.
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// What a `LineParser` found in a line, either can be missing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Parsed {
+    pub time: Option<Timestamp>,
+    pub level: Option<Level>,
+}
+
+// The time at the start of the line, then the level in upper case, right after it or
+// within the next few words so a thread or logger name can come first. Lower case
+// levels aren't looked for as they'd be found in the message just as often.
+const COMMON: &str = r"^\[?(?P<time>(?:\d{4}-\d{2}-\d{2}[T ]|[A-Z][a-z]{2} +\d{1,2} )?\d{1,2}:\d{2}(?::\d{2})?(?:[.,]\d+)?)(?:Z|[+-]\d{2}:?\d{2})?\]?(?:\s+(?:.{0,40}?[\s\[|:])??\[?(?P<level>TRACE|DEBUG|INFO|WARN(?:ING)?|ERROR|FATAL|CRITICAL)\b)?";
+
+// the keys JSON loggers usually put the time and level under
+const JSON_TIME_KEYS: [&str; 5] = ["time", "timestamp", "ts", "@timestamp", "date"];
+const JSON_LEVEL_KEYS: [&str; 4] = ["level", "lvl", "severity", "log.level"];
+
+// A `LineFormat` ready to parse lines with, the regex is only compiled once per search.
+#[derive(Debug, Clone)]
+pub enum LineParser {
+    Regex(Regex),
+    JsonLines,
+}
+
+impl LineParser {
+    pub fn new(format: &LineFormat) -> Result<LineParser, String> {
+        match format {
+            LineFormat::Common => Ok(LineParser::Regex(Regex::new(COMMON).expect("the common format is valid"))),
+            LineFormat::JsonLines => Ok(LineParser::JsonLines),
+            LineFormat::Custom(pattern) => {
+                let re = RegexBuilder::new(pattern).build().map_err(|e| e.to_string())?;
+                if !re.capture_names().flatten().any(|name| name == "time" || name == "level") {
+                    return Err("The format needs a (?P<time>...) or (?P<level>...) group".to_string());
+                }
+                Ok(LineParser::Regex(re))
+            }
+        }
+    }
+
+    pub fn parse(&self, line: &[u8]) -> Parsed {
+        match self {
+            LineParser::Regex(re) => match re.captures(line) {
+                Some(captures) => {
+                    let text = |name| captures.name(name).map(|m| String::from_utf8_lossy(m.as_bytes()));
+                    Parsed {
+                        time: text("time").and_then(|time| Timestamp::parse(&time)),
+                        level: text("level").and_then(|level| Level::parse(&level)),
+                    }
+                }
+                None => Parsed::default(),
+            },
+            LineParser::JsonLines => {
+                let value: serde_json::Value = match serde_json::from_slice(line) {
+                    Ok(value) => value,
+                    Err(_) => return Parsed::default(),
+                };
+                let find = |keys: &[&str]| keys.iter().find_map(|key| value.get(key));
+                Parsed {
+                    time: find(&JSON_TIME_KEYS).and_then(|time| match time {
+                        serde_json::Value::String(time) => Timestamp::parse(time),
+                        serde_json::Value::Number(epoch) => epoch.as_f64().map(Timestamp::from_epoch),
+                        _ => None,
+                    }),
+                    level: find(&JSON_LEVEL_KEYS).and_then(|level| level.as_str()).and_then(Level::parse),
+                }
+            }
+        }
+    }
+}
+
+// Which lines a search looks at, by their level and time. Lines without a level
+// or time can't be shown to pass, so they are left out when filtering on it.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    parser: LineParser,
+    // this level and the ones above it
+    level: Option<Level>,
+    from: Option<Timestamp>,
+    // the last second that is still in range
+    to: Option<Timestamp>,
+}
+
+impl Filter {
+    // `None` when there is nothing to filter on, so every line is searched.
+    pub fn new(format: &LineFormat, level: Option<Level>, from: &str, to: &str) -> Result<Option<Filter>, String> {
+        let bound = |text: &str, end: bool| -> Result<Option<Timestamp>, String> {
+            if text.trim().is_empty() {
+                return Ok(None);
+            }
+            match Timestamp::parse_bound(text, end) {
+                Some((mut timestamp, unsaid)) => {
+                    timestamp.seconds += unsaid;
+                    Ok(Some(timestamp))
+                }
+                None => Err(format!(
+                    "'{}' isn't a time, use something like 02:15, 02:15:30 or 2024-01-02 02:15",
+                    text.trim()
+                )),
+            }
+        };
+        let from = bound(from, false)?;
+        let to = bound(to, true)?;
+        if level.is_none() && from.is_none() && to.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Filter {
+            parser: LineParser::new(format)?,
+            level,
+            from,
+            to,
+        }))
+    }
+
+    pub fn allows(&self, line: &[u8]) -> bool {
+        let parsed = self.parser.parse(line);
+        if let Some(level) = self.level {
+            if parsed.level.is_none_or(|found| found < level) {
+                return false;
+            }
+        }
+        if self.from.is_some() || self.to.is_some() {
+            let time = match parsed.time {
+                Some(time) => time,
+                None => return false,
+            };
+            if self.from.is_some_and(|from| time.compare(&from) == Ordering::Less)
+                || self.to.is_some_and(|to| time.compare(&to) == Ordering::Greater)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: Option<(i64, u32, u32)>, seconds: u32) -> Timestamp {
+        Timestamp { date, seconds }
+    }
+
+    #[test]
+    fn bounds_cover_what_the_text_leaves_unsaid() {
+        assert_eq!(Timestamp::parse_bound("02:15", false), Some((at(None, 8100), 0)));
+        assert_eq!(Timestamp::parse_bound("02:15", true), Some((at(None, 8100), 59)));
+        assert_eq!(Timestamp::parse_bound("02:15:30", true), Some((at(None, 8130), 0)));
+        let day = Some((2024, 1, 2));
+        assert_eq!(Timestamp::parse_bound("2024-01-02", false), Some((at(day, 0), 0)));
+        assert_eq!(Timestamp::parse_bound("2024-01-02", true), Some((at(day, 0), 86_399)));
+        assert_eq!(Timestamp::parse_bound(" 2024-01-02T02:15:30.123Z ", false), Some((at(day, 8130), 0)));
+        // syslog's month and day are left out, there is no year to go with them
+        assert_eq!(Timestamp::parse_bound("Jan  2 02:15:30", false), Some((at(None, 8130), 0)));
+        for text in ["", "soon", "25:00", "02:61", "2024-13-02"] {
+            assert_eq!(Timestamp::parse_bound(text, false), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn epoch_times_are_seconds_or_milliseconds() {
+        let parser = LineParser::new(&LineFormat::JsonLines).unwrap();
+        let expected = Parsed {
+            time: Some(at(Some((2024, 1, 2)), 8100)),
+            level: Some(Level::Warn),
+        };
+        assert_eq!(parser.parse(br#"{"ts": 1704161700, "level": "warning"}"#), expected);
+        assert_eq!(parser.parse(br#"{"ts": 1704161700000, "lvl": "WARN"}"#), expected);
+        assert_eq!(parser.parse(b"not json"), Parsed::default());
+    }
+
+    #[test]
+    fn filters_on_level_and_time() {
+        let filter = Filter::new(&LineFormat::Common, Some(Level::Warn), "02:00", "02:15").unwrap().unwrap();
+        assert!(filter.allows(b"2024-01-02 02:03:04,567 [main] ERROR timeout"));
+        assert!(filter.allows(b"[02:10:00] WARN retrying"));
+        // the whole minute the range ends in is still in it
+        assert!(filter.allows(b"2024-01-02 02:15:59 WARN late"));
+        assert!(!filter.allows(b"2024-01-02 02:16:00 ERROR too late"));
+        assert!(!filter.allows(b"2024-01-02 01:59:59 ERROR too early"));
+        assert!(!filter.allows(b"2024-01-02 02:03:04 INFO not bad enough"));
+        // without a time or level it can't be shown to be in range
+        assert!(!filter.allows(b"ERROR no time"));
+        assert!(!filter.allows(b"02:03:04 error in lower case"));
+    }
+
+    #[test]
+    fn dates_are_compared_when_both_sides_have_one() {
+        let filter = Filter::new(&LineFormat::Common, None, "2024-01-02", "2024-01-02").unwrap().unwrap();
+        assert!(filter.allows(b"2024-01-02 00:00:00 first"));
+        assert!(filter.allows(b"2024-01-02 23:59:59 last"));
+        assert!(!filter.allows(b"2024-01-03 00:00:00 the day after"));
+        // only the time of day is known, and midnight is in range
+        assert!(filter.allows(b"00:00:00 no date"));
+    }
+
+    #[test]
+    fn nothing_to_filter_on_is_no_filter() {
+        assert!(Filter::new(&LineFormat::Common, None, " ", "").unwrap().is_none());
+        let error = Filter::new(&LineFormat::Common, None, "soon", "").unwrap_err();
+        assert!(error.starts_with("'soon' isn't a time"), "{}", error);
+        assert!(Filter::new(&LineFormat::Custom(r"^\S+".to_string()), Some(Level::Info), "", "").is_err());
+    }
+}
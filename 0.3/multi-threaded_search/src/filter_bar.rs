@@ -0,0 +1,201 @@
+use std::fmt;
+use iced::{pick_list, text_input, Column, Element, Length, PickList, Row, Text, TextInput};
+use serde::{Deserialize, Serialize};
+
+use multi_threaded_search::filter::{Filter, Level, LineFormat};
+
+use crate::ERROR_COLOR;
+
+// The row under the search bars for only searching lines of some level or time.
+// Like `SearchBar` its state lives in the main state and it is turned into part
+// of the `Search` when one starts, see `State::search`.
+#[derive(Debug, Clone, Default)]
+pub struct FilterBar {
+    format: FormatChoice,
+    // kept when another format is picked, so going back to it doesn't lose it
+    custom: String,
+    level: LevelChoice,
+    from: String,
+    to: String,
+    pub error: Option<String>,
+    formats: pick_list::State<FormatChoice>,
+    custom_state: text_input::State,
+    levels: pick_list::State<LevelChoice>,
+    from_state: text_input::State,
+    to_state: text_input::State,
+}
+
+#[derive(Debug, Clone)]
+pub enum FilterMessage {
+    SetFormat(FormatChoice),
+    CustomChanged(String),
+    SetLevel(LevelChoice),
+    FromChanged(String),
+    ToChanged(String),
+}
+
+// A filter as it is kept with a saved search.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedFilter {
+    pub format: LineFormat,
+    pub level: Option<Level>,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FormatChoice {
+    #[default]
+    Common,
+    JsonLines,
+    Custom,
+}
+
+impl FormatChoice {
+    const ALL: [FormatChoice; 3] = [FormatChoice::Common, FormatChoice::JsonLines, FormatChoice::Custom];
+}
+
+impl fmt::Display for FormatChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FormatChoice::Common => "time level message",
+            FormatChoice::JsonLines => "JSON lines",
+            FormatChoice::Custom => "Custom regex",
+        })
+    }
+}
+
+// the lowest level searched, `None` for every line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LevelChoice(Option<Level>);
+
+impl LevelChoice {
+    const ALL: [LevelChoice; 7] = [
+        LevelChoice(None),
+        LevelChoice(Some(Level::Trace)),
+        LevelChoice(Some(Level::Debug)),
+        LevelChoice(Some(Level::Info)),
+        LevelChoice(Some(Level::Warn)),
+        LevelChoice(Some(Level::Error)),
+        LevelChoice(Some(Level::Fatal)),
+    ];
+}
+
+impl fmt::Display for LevelChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            None => f.write_str("Any level"),
+            Some(Level::Fatal) => write!(f, "{}", Level::Fatal),
+            Some(level) => write!(f, "{} and above", level),
+        }
+    }
+}
+
+impl FilterBar {
+    pub fn update(&mut self, message: FilterMessage) {
+        match message {
+            FilterMessage::SetFormat(format) => self.format = format,
+            FilterMessage::CustomChanged(custom) => self.custom = custom,
+            FilterMessage::SetLevel(level) => self.level = level,
+            FilterMessage::FromChanged(from) => self.from = from,
+            FilterMessage::ToChanged(to) => self.to = to,
+        }
+    }
+
+    // whether any lines are being left out, the format alone doesn't
+    pub fn is_active(&self) -> bool {
+        self.level.0.is_some() || !self.from.trim().is_empty() || !self.to.trim().is_empty()
+    }
+
+    fn format(&self) -> LineFormat {
+        match self.format {
+            FormatChoice::Common => LineFormat::Common,
+            FormatChoice::JsonLines => LineFormat::JsonLines,
+            FormatChoice::Custom => LineFormat::Custom(self.custom.clone()),
+        }
+    }
+
+    pub fn filter(&self) -> Result<Option<Filter>, String> {
+        Filter::new(&self.format(), self.level.0, &self.from, &self.to)
+    }
+
+    pub fn saved(&self) -> SavedFilter {
+        SavedFilter {
+            format: self.format(),
+            level: self.level.0,
+            from: self.from.clone(),
+            to: self.to.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, saved: SavedFilter) {
+        self.format = match saved.format {
+            LineFormat::Common => FormatChoice::Common,
+            LineFormat::JsonLines => FormatChoice::JsonLines,
+            LineFormat::Custom(custom) => {
+                self.custom = custom;
+                FormatChoice::Custom
+            }
+        };
+        self.level = LevelChoice(saved.level);
+        self.from = saved.from;
+        self.to = saved.to;
+    }
+
+    pub fn unfocus(&mut self) {
+        self.custom_state.unfocus();
+        self.from_state.unfocus();
+        self.to_state.unfocus();
+    }
+
+    pub fn view(&mut self) -> Element<'_, FilterMessage> {
+        let mut row = Row::new()
+            .spacing(10)
+            .push(Text::new("Lines:"))
+            .push(PickList::new(
+                &mut self.formats,
+                &FormatChoice::ALL[..],
+                Some(self.format),
+                FilterMessage::SetFormat,
+            ));
+        if self.format == FormatChoice::Custom {
+            row = row.push(
+                TextInput::new(
+                    &mut self.custom_state,
+                    "(?P<time>\\S+) (?P<level>\\w+)",
+                    &self.custom,
+                    FilterMessage::CustomChanged,
+                )
+                .width(Length::Units(200))
+                .padding(5),
+            );
+        }
+        let row = row
+            .push(PickList::new(
+                &mut self.levels,
+                &LevelChoice::ALL[..],
+                Some(self.level),
+                FilterMessage::SetLevel,
+            ))
+            .push(Text::new("from"))
+            .push(
+                TextInput::new(&mut self.from_state, "02:00", &self.from, FilterMessage::FromChanged)
+                    .width(Length::Units(150))
+                    .padding(5),
+            )
+            .push(Text::new("to"))
+            .push(
+                TextInput::new(&mut self.to_state, "02:15", &self.to, FilterMessage::ToChanged)
+                    .width(Length::Units(150))
+                    .padding(5),
+            );
+        match &self.error {
+            Some(error) => Column::new()
+                .push(row)
+                .push(Text::new(error).size(16).color(ERROR_COLOR))
+                .into(),
+            None => row.into(),
+        }
+    }
+}
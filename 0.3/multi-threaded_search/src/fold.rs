@@ -0,0 +1,59 @@
+use std::iter;
+use caseless::Caseless;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// How the text typed into the search bars is compared with the files, picked per search.
+// Regexes always use `Case`, the others only apply to plain terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Folding {
+    // simple case insensitive matching, one character for another
+    #[default]
+    Case,
+    // full Unicode case folding, so "STRASSE" finds "straße"
+    Full,
+    // full case folding of the NFKD form with the accents taken off, so "cafe" finds "Café"
+    Accents,
+}
+
+impl Folding {
+    pub fn fold(self, text: &str) -> String {
+        let mut folded = String::with_capacity(text.len());
+        for c in text.chars() {
+            self.fold_char(c, &mut folded);
+        }
+        folded
+    }
+
+    // Folding can change the length of a string, so alongside the folded text
+    // return where each of its bytes came from in the original.
+    pub fn fold_with_offsets(self, text: &str) -> (String, Vec<usize>) {
+        let mut folded = String::with_capacity(text.len());
+        let mut offsets = Vec::with_capacity(text.len());
+        for (i, c) in text.char_indices() {
+            let start = folded.len();
+            self.fold_char(c, &mut folded);
+            offsets.extend(iter::repeat_n(i, folded.len() - start));
+        }
+        (folded, offsets)
+    }
+
+    // Every one of these folds a character without looking at its neighbours,
+    // which is what lets `fold_with_offsets` map the result back.
+    fn fold_char(self, c: char, folded: &mut String) {
+        match self {
+            Folding::Case => folded.extend(c.to_lowercase()),
+            Folding::Full => folded.extend(iter::once(c).default_case_fold()),
+            Folding::Accents => folded.extend(
+                iter::once(c)
+                    .nfkd()
+                    .filter(|c| !is_combining_mark(*c))
+                    .default_case_fold()
+                    // folding can give back characters that decompose further
+                    .nfkd()
+                    .filter(|c| !is_combining_mark(*c)),
+            ),
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::filter::civil_from_days;
+
+// Test files to search, made up from a seed so the same settings always give
+// byte for byte the same files. Benchmarks can then be compared across runs
+// and machines, and each file only depends on the seed and its number so they
+// can be written in any order, or in parallel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corpus {
+    pub seed: u64,
+    pub files: usize,
+    // in bytes, every file is at least `min_size` and at most a line over `max_size`
+    pub min_size: u64,
+    pub max_size: u64,
+    pub sizes: Sizes,
+    // how many directories deep the files are put, 0 for all in the root
+    pub depth: usize,
+    pub style: Style,
+}
+
+// How the file sizes are spread between the smallest and biggest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sizes {
+    Uniform,
+    // mostly small files with the odd big one, like real log directories
+    LongTail,
+}
+
+// What the lines in the files look like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Prose,
+    Syslog,
+    JsonLines,
+    // `time level message` lines with Java style stack traces after some of the errors
+    StackTraces,
+}
+
+// each directory level has this many directories to pick from
+const FANOUT: u64 = 4;
+// the generated logs start at 2024-01-01 00:00:00, in days since 1970
+const START_DAY: i64 = 19_723;
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+const HOSTS: [&str; 4] = ["web-1", "web-2", "db-1", "worker-1"];
+const SERVICES: [&str; 5] = ["api", "auth", "billing", "search", "scheduler"];
+// with how many in a hundred lines have each level
+const LEVELS: [(&str, u64); 5] = [("DEBUG", 15), ("INFO", 60), ("WARN", 15), ("ERROR", 9), ("FATAL", 1)];
+// `{n}` is replaced with a number, `{service}` with a service and `{word}` with a word
+const MESSAGES: [&str; 12] = [
+    "request served in {n}ms",
+    "connection reset by peer",
+    "timeout after {n}ms talking to {service}",
+    "retrying in {n}s",
+    "user {n} logged in",
+    "cache miss for key {word}",
+    "connection refused by {service}",
+    "queue depth is {n}",
+    "slow query took {n}ms",
+    "disk usage at {n}%",
+    "scheduled job {word} finished",
+    "config reloaded by {service}",
+];
+const EXCEPTIONS: [&str; 4] = [
+    "java.net.SocketTimeoutException: connect timed out",
+    "java.lang.NullPointerException",
+    "java.io.IOException: Connection reset by peer",
+    "java.lang.IllegalStateException: pool exhausted",
+];
+const WORDS: [&str; 64] = [
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do",
+    "eiusmod", "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua", "enim",
+    "ad", "minim", "veniam", "quis", "nostrud", "exercitation", "ullamco", "laboris", "nisi", "aliquip",
+    "ex", "ea", "commodo", "consequat", "duis", "aute", "irure", "in", "reprehenderit", "voluptate",
+    "velit", "esse", "cillum", "fugiat", "nulla", "pariatur", "excepteur", "sint", "occaecat", "cupidatat",
+    "non", "proident", "sunt", "culpa", "qui", "officia", "deserunt", "mollit", "anim", "id",
+    "est", "laborum", "timeout", "retry",
+];
+
+impl Default for Corpus {
+    fn default() -> Self {
+        Corpus {
+            seed: 1,
+            files: 100,
+            min_size: 10_000,
+            max_size: 100_000,
+            sizes: Sizes::Uniform,
+            depth: 0,
+            style: Style::Prose,
+        }
+    }
+}
+
+impl Corpus {
+    // Where file `index` goes under the root. The number in the name keeps every name different.
+    pub fn path(&self, index: usize) -> PathBuf {
+        let mut rng = self.rng(index);
+        let mut path = PathBuf::new();
+        for _ in 0..self.depth {
+            path.push(format!("dir_{}", rng.below(FANOUT)));
+        }
+        path.push(format!("file_{:05}.{}", index, self.style.extension()));
+        path
+    }
+
+    // Write file `index` under `root`, returning how big it is.
+    pub fn write(&self, root: &Path, index: usize) -> io::Result<u64> {
+        let path = root.join(self.path(index));
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = BufWriter::new(File::create(&path)?);
+        // a stream of its own, so the path taking more or fewer numbers doesn't change the contents
+        let mut rng = self.rng(index).split();
+        let size = self.size(&mut rng);
+        // each file starts somewhere in the first day
+        let start = rng.below(86_400) as i64;
+        let mut lines = Lines {
+            rng,
+            style: self.style,
+            seconds: start,
+            millis: 0,
+        };
+        let mut written = 0;
+        let mut line = String::new();
+        while written < size {
+            line.clear();
+            lines.next(&mut line);
+            line.push('\n');
+            out.write_all(line.as_bytes())?;
+            written += line.len() as u64;
+        }
+        out.flush()?;
+        Ok(written)
+    }
+
+    fn rng(&self, index: usize) -> Rng {
+        Rng::new(self.seed ^ (index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    // Only integer maths, floating point could round differently on another machine.
+    fn size(&self, rng: &mut Rng) -> u64 {
+        let (min, max) = (self.min_size.min(self.max_size), self.max_size.max(self.min_size));
+        let range = (max - min) as u128;
+        let offset = match self.sizes {
+            Sizes::Uniform => range * rng.below(1_000_001) as u128 / 1_000_000,
+            // the product of three draws is usually small but now and then close to 1
+            Sizes::LongTail => {
+                let draws = (0..3).map(|_| rng.below(1001) as u128).product::<u128>();
+                range * draws / 1_000_000_000
+            }
+        };
+        min + offset as u64
+    }
+}
+
+// Writes one line after another in a style, moving the clock on as it goes.
+struct Lines {
+    rng: Rng,
+    style: Style,
+    seconds: i64,
+    millis: u64,
+}
+
+impl Lines {
+    fn next(&mut self, line: &mut String) {
+        self.millis += self.rng.below(3000);
+        self.seconds += (self.millis / 1000) as i64;
+        self.millis %= 1000;
+        match self.style {
+            Style::Prose => {
+                let words = 8 + self.rng.below(10);
+                for i in 0..words {
+                    if i > 0 {
+                        line.push(' ');
+                    }
+                    line.push_str(self.rng.pick(&WORDS));
+                }
+                line.push('.');
+            }
+            Style::Syslog => {
+                let (_, month, day) = civil_from_days(START_DAY + self.seconds / 86_400);
+                let host = self.rng.pick(&HOSTS);
+                let service = self.rng.pick(&SERVICES);
+                let pid = 1000 + self.rng.below(9000);
+                let level = self.level();
+                let message = self.message();
+                line.push_str(&format!(
+                    "{} {:>2} {} {} {}[{}]: {} {}",
+                    MONTHS[month as usize - 1],
+                    day,
+                    self.time(),
+                    host,
+                    service,
+                    pid,
+                    level,
+                    message
+                ));
+            }
+            Style::JsonLines => {
+                let level = self.level().to_lowercase();
+                let service = self.rng.pick(&SERVICES);
+                let message = self.message();
+                let json = serde_json::json!({
+                    "time": format!("{}T{}.{:03}Z", self.date(), self.time(), self.millis),
+                    "level": level,
+                    "service": service,
+                    "msg": message,
+                });
+                line.push_str(&json.to_string());
+            }
+            Style::StackTraces => {
+                let thread = self.rng.below(8);
+                let level = self.level();
+                let message = self.message();
+                line.push_str(&format!(
+                    "{} {},{:03} [worker-{}] {} {}",
+                    self.date(),
+                    self.time(),
+                    self.millis,
+                    thread,
+                    level,
+                    message
+                ));
+                if level == "ERROR" || level == "FATAL" {
+                    self.stack_trace(line);
+                }
+            }
+        }
+    }
+
+    fn stack_trace(&mut self, line: &mut String) {
+        line.push('\n');
+        line.push_str(self.rng.pick(&EXCEPTIONS));
+        for _ in 0..3 + self.rng.below(6) {
+            let service = self.rng.pick(&SERVICES);
+            let class = capitalize(self.rng.pick(&WORDS));
+            let method = self.rng.pick(&WORDS);
+            let number = 10 + self.rng.below(500);
+            line.push_str(&format!(
+                "\n\tat com.example.{}.{}.{}({}.java:{})",
+                service, class, method, class, number
+            ));
+        }
+    }
+
+    fn level(&mut self) -> &'static str {
+        let mut roll = self.rng.below(100);
+        for (level, chance) in LEVELS {
+            if roll < chance {
+                return level;
+            }
+            roll -= chance;
+        }
+        "INFO"
+    }
+
+    fn message(&mut self) -> String {
+        let mut message = self.rng.pick(&MESSAGES).to_string();
+        if message.contains("{n}") {
+            message = message.replace("{n}", &self.rng.below(5000).to_string());
+        }
+        if message.contains("{service}") {
+            message = message.replace("{service}", self.rng.pick(&SERVICES));
+        }
+        if message.contains("{word}") {
+            message = message.replace("{word}", self.rng.pick(&WORDS));
+        }
+        message
+    }
+
+    fn date(&self) -> String {
+        let (year, month, day) = civil_from_days(START_DAY + self.seconds / 86_400);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    fn time(&self) -> String {
+        let seconds = self.seconds % 86_400;
+        format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+// SplitMix64. `rand`'s generators are free to change what they give between
+// versions, this can't, so a seed means the same files for good.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        let mut rng = Rng(seed);
+        rng.next();
+        rng
+    }
+
+    // a new generator that goes its own way from here
+    fn split(&mut self) -> Rng {
+        Rng::new(self.next())
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // from 0 up to but not including `n`
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next() as u128 * n as u128) >> 64) as u64
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+impl Sizes {
+    pub const ALL: [Sizes; 2] = [Sizes::Uniform, Sizes::LongTail];
+
+    pub fn parse(name: &str) -> Option<Sizes> {
+        match name {
+            "uniform" => Some(Sizes::Uniform),
+            "long-tail" => Some(Sizes::LongTail),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Sizes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Sizes::Uniform => "Evenly spread sizes",
+            Sizes::LongTail => "Mostly small sizes",
+        })
+    }
+}
+
+impl Style {
+    pub const ALL: [Style; 4] = [Style::Prose, Style::Syslog, Style::JsonLines, Style::StackTraces];
+
+    pub fn parse(name: &str) -> Option<Style> {
+        match name {
+            "prose" => Some(Style::Prose),
+            "syslog" => Some(Style::Syslog),
+            "json" => Some(Style::JsonLines),
+            "stack-traces" => Some(Style::StackTraces),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Style::Prose => "txt",
+            Style::JsonLines => "jsonl",
+            Style::Syslog | Style::StackTraces => "log",
+        }
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Style::Prose => "Prose",
+            Style::Syslog => "Syslog",
+            Style::JsonLines => "JSON lines",
+            Style::StackTraces => "Stack traces",
+        })
+    }
+}
+
+// `70kb`, `1.5mb` or a number of bytes, in powers of 1000 like the stats.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim().to_lowercase();
+    let (number, unit) = match text.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => text.split_at(i),
+        None => (&text[..], ""),
+    };
+    let unit = match unit.trim() {
+        "" | "b" => 1,
+        "kb" | "k" => 1_000,
+        "mb" | "m" => 1_000_000,
+        "gb" | "g" => 1_000_000_000,
+        _ => return Err(format!("'{}' isn't a size, use something like 500, 70kb or 2mb", text)),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(number) if number >= 0.0 => Ok((number * unit as f64) as u64),
+        _ => Err(format!("'{}' isn't a size, use something like 500, 70kb or 2mb", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn corpus(seed: u64, style: Style) -> Corpus {
+        Corpus {
+            seed,
+            files: 8,
+            min_size: 2_000,
+            max_size: 20_000,
+            sizes: Sizes::LongTail,
+            depth: 2,
+            style,
+        }
+    }
+
+    // an empty directory of its own for each test, tests run in parallel
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("multi-threaded_search-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // every file of `corpus` written in `order`, as its path and contents
+    fn write_all(corpus: &Corpus, root: &Path, order: impl Iterator<Item = usize>) -> Vec<(PathBuf, Vec<u8>)> {
+        for index in order {
+            corpus.write(root, index).unwrap();
+        }
+        (0..corpus.files)
+            .map(|index| {
+                let path = corpus.path(index);
+                let contents = fs::read(root.join(&path)).unwrap();
+                (path, contents)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_files_in_any_order() {
+        for style in Style::ALL {
+            let corpus = corpus(42, style);
+            let (a, b) = (temp_dir("same-a"), temp_dir("same-b"));
+            let first = write_all(&corpus, &a, 0..corpus.files);
+            let second = write_all(&corpus, &b, (0..corpus.files).rev());
+            assert!(first == second, "{} files differ between runs", style);
+            fs::remove_dir_all(a).unwrap();
+            fs::remove_dir_all(b).unwrap();
+        }
+    }
+
+    // Pinned so a change to the generator that changes what a seed makes can't go unnoticed,
+    // benchmarks run on files made before it would no longer be comparable.
+    #[test]
+    fn a_seed_always_makes_the_same_file() {
+        let corpus = corpus(42, Style::Syslog);
+        let root = temp_dir("pinned");
+        assert_eq!(corpus.path(3), Path::new("dir_3/dir_0/file_00003.log"));
+        assert_eq!(corpus.write(&root, 3).unwrap(), 8039);
+        let contents = fs::read_to_string(root.join(corpus.path(3))).unwrap();
+        assert_eq!(
+            contents.lines().next(),
+            Some("Jan  1 09:50:51 worker-1 auth[3806]: INFO cache miss for key deserunt")
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn different_seeds_give_different_files() {
+        let (a, b) = (temp_dir("seed-a"), temp_dir("seed-b"));
+        let first = write_all(&corpus(1, Style::Syslog), &a, 0..8);
+        let second = write_all(&corpus(2, Style::Syslog), &b, 0..8);
+        assert!(first.iter().zip(&second).all(|(a, b)| a.1 != b.1));
+        fs::remove_dir_all(a).unwrap();
+        fs::remove_dir_all(b).unwrap();
+    }
+
+    #[test]
+    fn files_are_the_size_asked_for() {
+        let corpus = Corpus { files: 20, ..corpus(7, Style::StackTraces) };
+        let root = temp_dir("sizes");
+        for index in 0..corpus.files {
+            let written = corpus.write(&root, index).unwrap();
+            assert_eq!(written, fs::metadata(root.join(corpus.path(index))).unwrap().len());
+            // the last line can go over, a stack trace is written as one line
+            assert!(written >= corpus.min_size && written < corpus.max_size + 2_000, "{} bytes", written);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn sizes_parse() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("70kb"), Ok(70_000));
+        assert_eq!(parse_size("1.5 MB"), Ok(1_500_000));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("-1kb").is_err());
+    }
+}
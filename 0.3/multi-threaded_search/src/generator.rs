@@ -0,0 +1,155 @@
+use iced::{button, pick_list, text_input, Button, Column, Element, Length, PickList, Row, Text, TextInput};
+
+use multi_threaded_search::generate::{parse_size, Corpus, Sizes, Style};
+
+use crate::ERROR_COLOR;
+
+// The settings for making test files, opened from the create button.
+// The numbers are kept as typed and only read when `Generate` is pressed.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    pub open: bool,
+    seed: String,
+    files: String,
+    min_size: String,
+    max_size: String,
+    depth: String,
+    sizes: Sizes,
+    style: Style,
+    pub error: Option<String>,
+    seed_state: text_input::State,
+    files_state: text_input::State,
+    min_size_state: text_input::State,
+    max_size_state: text_input::State,
+    depth_state: text_input::State,
+    sizes_list: pick_list::State<Sizes>,
+    styles: pick_list::State<Style>,
+    generate_button: button::State,
+}
+
+#[derive(Debug, Clone)]
+pub enum GeneratorMessage {
+    SeedChanged(String),
+    FilesChanged(String),
+    MinSizeChanged(String),
+    MaxSizeChanged(String),
+    DepthChanged(String),
+    SetSizes(Sizes),
+    SetStyle(Style),
+    Generate,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        let corpus = Corpus::default();
+        Generator {
+            open: false,
+            seed: corpus.seed.to_string(),
+            files: corpus.files.to_string(),
+            min_size: format!("{}kb", corpus.min_size / 1000),
+            max_size: format!("{}kb", corpus.max_size / 1000),
+            depth: corpus.depth.to_string(),
+            sizes: corpus.sizes,
+            style: corpus.style,
+            error: None,
+            seed_state: text_input::State::new(),
+            files_state: text_input::State::new(),
+            min_size_state: text_input::State::new(),
+            max_size_state: text_input::State::new(),
+            depth_state: text_input::State::new(),
+            sizes_list: pick_list::State::default(),
+            styles: pick_list::State::default(),
+            generate_button: button::State::new(),
+        }
+    }
+}
+
+impl Generator {
+    pub fn update(&mut self, message: GeneratorMessage) {
+        match message {
+            GeneratorMessage::SeedChanged(seed) => self.seed = seed,
+            GeneratorMessage::FilesChanged(files) => self.files = files,
+            GeneratorMessage::MinSizeChanged(size) => self.min_size = size,
+            GeneratorMessage::MaxSizeChanged(size) => self.max_size = size,
+            GeneratorMessage::DepthChanged(depth) => self.depth = depth,
+            GeneratorMessage::SetSizes(sizes) => self.sizes = sizes,
+            GeneratorMessage::SetStyle(style) => self.style = style,
+            // handled by the app, which does the writing
+            GeneratorMessage::Generate => (),
+        }
+    }
+
+    pub fn corpus(&self) -> Result<Corpus, String> {
+        let number = |text: &str, what: &str| {
+            text.trim()
+                .parse()
+                .map_err(|_| format!("The {} needs to be a whole number, not '{}'", what, text.trim()))
+        };
+        Ok(Corpus {
+            seed: number(&self.seed, "seed")?,
+            files: number(&self.files, "number of files")? as usize,
+            min_size: parse_size(&self.min_size)?,
+            max_size: parse_size(&self.max_size)?,
+            sizes: self.sizes,
+            depth: number(&self.depth, "depth")? as usize,
+            style: self.style,
+        })
+    }
+
+    pub fn unfocus(&mut self) {
+        for state in [
+            &mut self.seed_state,
+            &mut self.files_state,
+            &mut self.min_size_state,
+            &mut self.max_size_state,
+            &mut self.depth_state,
+        ] {
+            state.unfocus();
+        }
+    }
+
+    // `generating` turns the button off until the files being written are done
+    pub fn view(&mut self, generating: bool) -> Element<'_, GeneratorMessage> {
+        let input = |state, value: &str, on_change: fn(String) -> GeneratorMessage| {
+            TextInput::new(state, "", value, on_change)
+                .width(Length::Units(80))
+                .padding(5)
+        };
+        let mut generate = Button::new(&mut self.generate_button, Text::new("Generate"));
+        if !generating {
+            generate = generate.on_press(GeneratorMessage::Generate);
+        }
+        let numbers = Row::new()
+            .spacing(10)
+            .push(Text::new("Files"))
+            .push(input(&mut self.files_state, &self.files, GeneratorMessage::FilesChanged))
+            .push(Text::new("from"))
+            .push(input(&mut self.min_size_state, &self.min_size, GeneratorMessage::MinSizeChanged))
+            .push(Text::new("to"))
+            .push(input(&mut self.max_size_state, &self.max_size, GeneratorMessage::MaxSizeChanged))
+            .push(Text::new("Directories deep"))
+            .push(input(&mut self.depth_state, &self.depth, GeneratorMessage::DepthChanged));
+        let choices = Row::new()
+            .spacing(10)
+            .push(Text::new("Seed"))
+            .push(input(&mut self.seed_state, &self.seed, GeneratorMessage::SeedChanged))
+            .push(PickList::new(
+                &mut self.sizes_list,
+                &Sizes::ALL[..],
+                Some(self.sizes),
+                GeneratorMessage::SetSizes,
+            ))
+            .push(PickList::new(
+                &mut self.styles,
+                &Style::ALL[..],
+                Some(self.style),
+                GeneratorMessage::SetStyle,
+            ))
+            .push(generate);
+        let column = Column::new().spacing(10).push(numbers).push(choices);
+        match &self.error {
+            Some(error) => column.push(Text::new(error).size(16).color(ERROR_COLOR)).into(),
+            None => column.into(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::ops::Range;
+
+use crate::query::Query;
+
+// how many matching lines are shown under a collapsed `Log`
+pub const SNIPPET_LINES: usize = 3;
+// longer snippet lines are cut down to the part around the first match
+const SNIPPET_WIDTH: usize = 160;
+// Text can't wrap across a `Row` of differently coloured pieces,
+// so lines are broken up every this many characters instead
+const WRAP_WIDTH: usize = 90;
+
+// A line of a file along with where the query matched in it.
+#[derive(Debug, Clone)]
+pub struct Snippet {
+    pub line_number: usize,
+    pub text: String,
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl Snippet {
+    pub fn new(line_number: usize, text: &str, query: Option<&Query>) -> Self {
+        Snippet {
+            line_number,
+            text: text.to_string(),
+            highlights: query.map(|query| query.highlights(text)).unwrap_or_default(),
+        }
+    }
+
+    // A shortened snippet of the line for under a collapsed `Log`,
+    // or `None` if none of the query's terms are in the line.
+    // A query without any terms, when only filtering, previews every line.
+    pub fn preview(line_number: usize, text: &str, query: &Query) -> Option<Self> {
+        let snippet = Snippet::new(line_number, text, Some(query));
+        if snippet.highlights.is_empty() && !query.terms().is_empty() {
+            None
+        } else {
+            Some(snippet.shorten())
+        }
+    }
+
+    // Split the line into rows of at most `WRAP_WIDTH` characters,
+    // each row being pieces of text and whether that piece is highlighted.
+    pub fn wrap(&self) -> Vec<Vec<(String, bool)>> {
+        let mut rows: Vec<Vec<(String, bool)>> = vec![Vec::new()];
+        let mut width = 0;
+        let mut highlights = self.highlights.iter().peekable();
+        for (i, c) in self.text.char_indices() {
+            if width == WRAP_WIDTH {
+                rows.push(Vec::new());
+                width = 0;
+            }
+            while highlights.peek().is_some_and(|range| range.end <= i) {
+                highlights.next();
+            }
+            let highlighted = highlights.peek().is_some_and(|range| range.contains(&i));
+            let row = rows.last_mut().expect("there is always a row");
+            match row.last_mut() {
+                Some((piece, h)) if *h == highlighted => piece.push(c),
+                _ => row.push((c.to_string(), highlighted)),
+            }
+            width += 1;
+        }
+        rows
+    }
+
+    // cut a long line down to `SNIPPET_WIDTH` characters around its first highlight
+    fn shorten(mut self) -> Self {
+        if self.text.chars().count() <= SNIPPET_WIDTH {
+            return self;
+        }
+        let first = self.highlights.first().map_or(0, |range| range.start);
+        // start a little before the match so there is some context
+        let start = self.text[..first]
+            .char_indices()
+            .rev()
+            .nth(SNIPPET_WIDTH / 4)
+            .map_or(0, |(i, _)| i);
+        let end = self.text[start..]
+            .char_indices()
+            .nth(SNIPPET_WIDTH)
+            .map_or(self.text.len(), |(i, _)| start + i);
+        let prefix = if start > 0 { "…" } else { "" };
+        let suffix = if end < self.text.len() { "…" } else { "" };
+        let shift = prefix.len() as isize - start as isize;
+        self.highlights = self
+            .highlights
+            .into_iter()
+            .filter(|range| range.start < end && range.end > start)
+            .map(|range| {
+                let range = range.start.max(start)..range.end.min(end);
+                (range.start as isize + shift) as usize..(range.end as isize + shift) as usize
+            })
+            .collect();
+        self.text = format!("{}{}{}", prefix, &self.text[start..end], suffix);
+        self
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+
+use crate::files::{list_files, LogFile, Selection};
+use crate::query::{Query, Term};
+use crate::scan::LineReader;
+
+pub const INDEX: &str = "./logs.index";
+// bump this whenever the layout of `Index` changes so old files are rebuilt
+const VERSION: u32 = 2;
+// numbers the files saves are written to first, see `Index::save`
+static SAVES: AtomicUsize = AtomicUsize::new(0);
+
+// An inverted index of every word under the search roots, saved to `INDEX` between runs.
+// It is only used to skip files that can't possibly match a query, the files
+// that might match are still searched as normal. This keeps the index simple,
+// it doesn't need to know about regexes, NOT or where in the file words are.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    vocab: Vec<String>,
+    files: Vec<IndexedFile>,
+    // everything below is rebuilt from `vocab` and `files` after loading
+    #[serde(skip)]
+    by_path: HashMap<PathBuf, usize>,
+    #[serde(skip)]
+    postings: Vec<Vec<usize>>,
+    // the ids into `vocab` of the words with each run of three characters in them, in order
+    #[serde(skip)]
+    trigrams: HashMap<String, Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    size: u64,
+    // ids into `vocab` of every distinct word in the file
+    tokens: Vec<u32>,
+}
+
+// what happened during the last (re)build, shown in the speed row
+#[derive(Debug, Clone)]
+pub struct IndexStatus {
+    files: usize,
+    updated: usize,
+    removed: usize,
+    time: u128,
+    saved: Result<(), String>,
+}
+
+impl Index {
+    pub fn new() -> Self {
+        Index {
+            version: VERSION,
+            ..Index::default()
+        }
+    }
+
+    // Load the index saved by the last run, bringing it up to date with `roots`.
+    // Only files with a different modified time or size are read again.
+    pub async fn open(roots: Vec<PathBuf>) -> (Index, IndexStatus) {
+        let saved = File::open(INDEX)
+            .ok()
+            .and_then(|file| bincode::deserialize_from(BufReader::new(file)).ok())
+            .filter(|index: &Index| index.version == VERSION)
+            .unwrap_or_default();
+        saved.update(roots).await
+    }
+
+    // throw away everything and index every file again
+    pub async fn rebuild(roots: Vec<PathBuf>) -> (Index, IndexStatus) {
+        Index::new().update(roots).await
+    }
+
+    pub async fn update(self, roots: Vec<PathBuf>) -> (Index, IndexStatus) {
+        let start = Instant::now();
+        let mut old: HashMap<PathBuf, IndexedFile> = self
+            .files
+            .into_iter()
+            .map(|file| (file.path.clone(), file))
+            .collect();
+        let old_vocab = self.vocab;
+        // the vocab is rebuilt as files are added so words from deleted files are dropped
+        let mut vocab = Vocab::default();
+        let mut files = Vec::new();
+        let mut updated = 0;
+        // every file, the same index is used whichever files a search picks
+        for file in list_files(&roots, &Selection::default()) {
+            let path = file.path();
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let modified = metadata.modified().ok();
+            let size = metadata.len();
+            let tokens = match old.remove(&path) {
+                Some(file) if file.modified == modified && file.size == size => file
+                    .tokens
+                    .iter()
+                    .map(|&token| vocab.id(&old_vocab[token as usize]))
+                    .collect(),
+                _ => match read_words(&path) {
+                    Ok(words) => {
+                        updated += 1;
+                        words.iter().map(|word| vocab.id(word)).collect()
+                    }
+                    // unreadable files are left out, so they are always searched
+                    Err(_) => continue,
+                },
+            };
+            files.push(IndexedFile {
+                path,
+                modified,
+                size,
+                tokens,
+            });
+        }
+        let removed = old.len();
+        let mut index = Index {
+            version: VERSION,
+            vocab: vocab.words,
+            files,
+            by_path: HashMap::new(),
+            postings: Vec::new(),
+            trigrams: HashMap::new(),
+        };
+        index.build_postings();
+        let saved = if updated > 0 || removed > 0 {
+            index.save()
+        } else {
+            Ok(())
+        };
+        let status = IndexStatus {
+            files: index.files.len(),
+            updated,
+            removed,
+            time: start.elapsed().as_millis(),
+            saved,
+        };
+        (index, status)
+    }
+
+    // Written next to it and moved over, so a save that fails half way
+    // can't leave a broken index behind for the next start.
+    fn save(&self) -> Result<(), String> {
+        let save = SAVES.fetch_add(1, Ordering::Relaxed);
+        let tmp = format!("{}.{}.{}", INDEX, std::process::id(), save);
+        let mut writer = BufWriter::new(File::create(&tmp).map_err(|e| e.to_string())?);
+        bincode::serialize_into(&mut writer, self).map_err(|e| e.to_string())?;
+        writer
+            .flush()
+            .and_then(|_| fs::rename(&tmp, INDEX))
+            .map_err(|e| e.to_string())
+    }
+
+    fn build_postings(&mut self) {
+        self.postings = vec![Vec::new(); self.vocab.len()];
+        self.by_path = HashMap::with_capacity(self.files.len());
+        for (i, file) in self.files.iter().enumerate() {
+            self.by_path.insert(file.path.clone(), i);
+            for &token in &file.tokens {
+                self.postings[token as usize].push(i);
+            }
+        }
+        self.trigrams = HashMap::new();
+        for (id, word) in self.vocab.iter().enumerate() {
+            for trigram in trigrams(word) {
+                let words = self.trigrams.entry(trigram).or_default();
+                // a word can have the same three characters more than once
+                if words.last() != Some(&(id as u32)) {
+                    words.push(id as u32);
+                }
+            }
+        }
+    }
+
+    // The indexed files that could match `query`.
+    // `None` means the index can't rule anything out and every file has to be searched.
+    pub fn candidates(&self, query: &Query) -> Option<HashSet<usize>> {
+        match query {
+            Query::Term(Term::Plain(val, _)) => {
+                // a term can span several words, "connection reset" can only be in
+                // files that have a word containing "connection" and one containing "reset"
+                tokenize(val).fold(None, |found, piece| {
+                    let files = self.containing(piece);
+                    Some(match found {
+                        Some(found) => intersect(found, files),
+                        None => files,
+                    })
+                })
+            }
+            // the index can't tell which files *don't* contain something,
+            // and only knows the words lowercased, not folded
+            Query::Term(Term::Regex(_)) | Query::Term(Term::Folded(..)) | Query::Not(_) => None,
+            Query::And(queries) => queries
+                .iter()
+                .filter_map(|query| self.candidates(query))
+                .fold(None, |found, files| {
+                    Some(match found {
+                        Some(found) => intersect(found, files),
+                        None => files,
+                    })
+                }),
+            Query::Or(queries) => queries.iter().try_fold(HashSet::new(), |mut found, query| {
+                found.extend(self.candidates(query)?);
+                Some(found)
+            }),
+        }
+    }
+
+    // every file with a word containing `piece`, so "time" also finds "timeout"
+    fn containing(&self, piece: &str) -> HashSet<usize> {
+        self.maybe_containing(piece)
+            .into_iter()
+            .filter(|&word| self.vocab[word].contains(piece))
+            .flat_map(|word| self.postings[word].iter().copied())
+            .collect()
+    }
+
+    // The words that have every run of three characters in `piece`, which the ones
+    // containing it are among. A piece shorter than that could be in any word.
+    fn maybe_containing(&self, piece: &str) -> Vec<usize> {
+        let mut lists = Vec::new();
+        for trigram in trigrams(piece) {
+            match self.trigrams.get(&trigram) {
+                Some(words) => lists.push(words),
+                None => return Vec::new(),
+            }
+        }
+        lists.sort_by_key(|words| words.len());
+        match lists.split_first() {
+            Some((shortest, rest)) => shortest
+                .iter()
+                .filter(|word| rest.iter().all(|words| words.binary_search(word).is_ok()))
+                .map(|&word| word as usize)
+                .collect(),
+            None => (0..self.vocab.len()).collect(),
+        }
+    }
+
+    // The `files` the index can't rule out for `query`, in the same order.
+    pub fn narrow(&self, query: &Query, files: Vec<LogFile>) -> Vec<LogFile> {
+        match self.candidates(query) {
+            Some(candidates) => files
+                .into_iter()
+                .filter(|file| !self.rules_out(&file.path(), &candidates))
+                .collect(),
+            None => files,
+        }
+    }
+
+    // True if `path` is indexed, hasn't changed since and isn't one of the `candidates`.
+    // Files that are new or changed since the index was built are never ruled out.
+    pub fn rules_out(&self, path: &Path, candidates: &HashSet<usize>) -> bool {
+        match (self.by_path.get(path), fs::metadata(path)) {
+            (Some(&i), Ok(metadata)) => {
+                let file = &self.files[i];
+                file.modified == metadata.modified().ok()
+                    && file.size == metadata.len()
+                    && !candidates.contains(&i)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl IndexStatus {
+    pub fn text(&self) -> String {
+        let mut text = format!("Index: {} files in {}ms", self.files, self.time);
+        if self.updated > 0 || self.removed > 0 {
+            text.push_str(&format!(" ({} updated, {} removed)", self.updated, self.removed));
+        }
+        if let Err(e) = &self.saved {
+            text.push_str(&format!(", not saved: {}", e));
+        }
+        text
+    }
+}
+
+#[derive(Default)]
+struct Vocab {
+    words: Vec<String>,
+    ids: HashMap<String, u32>,
+}
+
+impl Vocab {
+    fn id(&mut self, word: &str) -> u32 {
+        if let Some(&id) = self.ids.get(word) {
+            return id;
+        }
+        let id = self.words.len() as u32;
+        self.words.push(word.to_string());
+        self.ids.insert(word.to_string(), id);
+        id
+    }
+}
+
+// every distinct word in a file, read a line at a time as log files can be huge
+fn read_words(path: &Path) -> std::io::Result<HashSet<String>> {
+    let mut lines = LineReader::open(path)?;
+    let mut words = HashSet::new();
+    while let Some((_, line)) = lines.next_line()? {
+        for word in tokenize(&String::from_utf8_lossy(line).to_lowercase()) {
+            if !words.contains(word) {
+                words.insert(word.to_string());
+            }
+        }
+    }
+    Ok(words)
+}
+
+// words are runs of letters and numbers, `text` should already be lowercased
+fn tokenize(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+// every run of three characters in `text`
+fn trigrams(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.windows(3).map(|trigram| trigram.iter().collect()).collect()
+}
+
+fn intersect(a: HashSet<usize>, b: HashSet<usize>) -> HashSet<usize> {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    small.into_iter().filter(|i| large.contains(i)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    // an index of the files written under `root`, built the way `update` does without saving it
+    fn indexed(root: &Path, files: &[(&str, &str)]) -> Index {
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(root).unwrap();
+        let mut vocab = Vocab::default();
+        let mut indexed = Vec::new();
+        for (name, text) in files {
+            let path = root.join(name);
+            fs::write(&path, text).unwrap();
+            let metadata = fs::metadata(&path).unwrap();
+            indexed.push(IndexedFile {
+                tokens: read_words(&path).unwrap().iter().map(|word| vocab.id(word)).collect(),
+                path,
+                modified: metadata.modified().ok(),
+                size: metadata.len(),
+            });
+        }
+        let mut index = Index {
+            version: VERSION,
+            vocab: vocab.words,
+            files: indexed,
+            ..Index::default()
+        };
+        index.build_postings();
+        index
+    }
+
+    fn candidates(index: &Index, query: &str) -> Option<Vec<String>> {
+        let mut names: Vec<String> = index
+            .candidates(&Query::parse(query).unwrap())?
+            .into_iter()
+            .map(|i| index.files[i].path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        Some(names)
+    }
+
+    #[test]
+    fn candidates_are_the_files_with_every_word() {
+        let root = env::temp_dir().join(format!("multi-threaded_search-index-{}", process::id()));
+        let index = indexed(
+            &root,
+            &[
+                ("a.log", "Connection reset by peer\n"),
+                ("b.log", "request timeout, retrying\n"),
+                ("c.log", "timeouts everywhere\nconnection refused\n"),
+            ],
+        );
+        let names = |names: &[&str]| Some(names.iter().map(|name| name.to_string()).collect::<Vec<_>>());
+        // a word containing the term is enough, whatever its case
+        assert_eq!(candidates(&index, "TIMEOUT"), names(&["b.log", "c.log"]));
+        assert_eq!(candidates(&index, "meou"), names(&["b.log", "c.log"]));
+        assert_eq!(candidates(&index, "et"), names(&["a.log", "b.log"]));
+        assert_eq!(candidates(&index, "connection AND timeout"), names(&["c.log"]));
+        assert_eq!(candidates(&index, "reset OR retry"), names(&["a.log", "b.log"]));
+        assert_eq!(candidates(&index, "nowhere"), names(&[]));
+        // it can't say which files don't have something
+        assert_eq!(candidates(&index, "NOT timeout"), None);
+        assert_eq!(candidates(&index, "timeout AND NOT retry"), names(&["b.log", "c.log"]));
+        assert_eq!(candidates(&index, "reset OR NOT timeout"), None);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn files_that_changed_since_are_never_ruled_out() {
+        let root = env::temp_dir().join(format!("multi-threaded_search-narrow-{}", process::id()));
+        let index = indexed(&root, &[("a.log", "timeout\n"), ("b.log", "fine\n"), ("c.log", "fine\n")]);
+        fs::write(root.join("c.log"), "a timeout since\n").unwrap();
+        fs::write(root.join("d.log"), "not indexed yet\n").unwrap();
+        let files: Vec<LogFile> = ["a.log", "b.log", "c.log", "d.log"]
+            .iter()
+            .map(|name| LogFile {
+                root: root.clone(),
+                relative: PathBuf::from(name),
+            })
+            .collect();
+        let narrowed = index.narrow(&Query::parse("timeout").unwrap(), files);
+        let names: Vec<_> = narrowed.iter().map(|file| file.relative.to_string_lossy().to_string()).collect();
+        assert_eq!(names, ["a.log", "c.log", "d.log"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,19 @@
+// The search itself, without any of the UI. Both the iced app and the headless
+// `--query` mode in `main.rs` are built on top of this, so they always match the
+// same way. Nothing in here knows about iced.
+
+pub mod files;
+pub mod filter;
+pub mod fold;
+pub mod generate;
+pub mod highlight;
+pub mod index;
+pub mod line_index;
+pub mod logger;
+pub mod matcher;
+pub mod query;
+pub mod scan;
+pub mod stats;
+
+// the default search root when none are given with `--root`
+pub const LOGS: &str = "./logs";
@@ -0,0 +1,284 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::highlight::Snippet;
+use crate::query::Query;
+use crate::scan::{Compression, FileError, LineReader};
+
+// where every this many'th line starts is kept, so reading from any line
+// only has to skip past fewer than this many
+const CHECKPOINT: usize = 1000;
+
+// Where the lines of a file are and which of them have the query in them, found
+// in one pass over the file so any part of it can then be read on its own. Only
+// this is kept in memory for an opened file, not the file itself.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    pub path: PathBuf,
+    pub lines: usize,
+    // the size of the file, after decompressing
+    pub bytes: u64,
+    // the numbers of the lines with any of the query's terms in them, in order
+    pub matches: Vec<usize>,
+    // the byte offset of line 1, 1 + CHECKPOINT, 1 + 2 * CHECKPOINT...
+    // Compressed files can't be seeked into, so they have none and are read from the start.
+    checkpoints: Vec<u64>,
+    // How many lines there are up to the last line ending and where it is. A last
+    // line without one may still be being written, so it is read again when following.
+    complete_lines: usize,
+    complete_bytes: u64,
+    // which file was read and how big it was on disk, to tell when it has been
+    // replaced, truncated or grown
+    file_id: Option<u64>,
+    file_len: u64,
+}
+
+// What has happened to a file since it was indexed, see `LineIndex::follow`.
+#[derive(Debug, Clone)]
+pub enum Followed {
+    Unchanged,
+    // lines were added to the end, the ones already there are where they were
+    Appended(LineIndex),
+    // truncated, or a new file in its place after the log was rotated
+    Replaced(LineIndex),
+}
+
+impl LineIndex {
+    pub fn build(path: &Path, query: Option<&Query>) -> Result<LineIndex, FileError> {
+        let metadata = fs::metadata(path)?;
+        let mut reader = LineReader::open(path)?;
+        if reader.is_binary()? {
+            return Err(FileError::Binary);
+        }
+        let mut index = LineIndex {
+            path: path.to_path_buf(),
+            lines: 0,
+            bytes: 0,
+            matches: Vec::new(),
+            checkpoints: Vec::new(),
+            complete_lines: 0,
+            complete_bytes: 0,
+            file_id: file_id(&metadata),
+            file_len: metadata.len(),
+        };
+        let seekable = Compression::of(path).is_none();
+        index.read_lines(&mut reader, seekable, query)?;
+        Ok(index)
+    }
+
+    // Catch up with the file after it has changed, only reading what was added
+    // to it if that's all that happened.
+    pub fn follow(&self, query: Option<&Query>) -> Result<Followed, FileError> {
+        let metadata = fs::metadata(&self.path)?;
+        let replaced = file_id(&metadata) != self.file_id || metadata.len() < self.file_len;
+        if !replaced && metadata.len() == self.file_len {
+            return Ok(Followed::Unchanged);
+        }
+        if replaced || Compression::of(&self.path).is_some() {
+            // compressed files have to be read from the start anyway
+            return Ok(Followed::Replaced(LineIndex::build(&self.path, query)?));
+        }
+        let mut index = self.clone();
+        index.file_len = metadata.len();
+        // take back a last line that had no line ending, the rest of it may be there now
+        let complete = index.complete_lines;
+        index.lines = complete;
+        index.bytes = index.complete_bytes;
+        index.matches.retain(|&line| line <= complete);
+        index.checkpoints.truncate(complete.div_ceil(CHECKPOINT));
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(index.complete_bytes))?;
+        index.read_lines(&mut LineReader::new(BufReader::new(file)), true, query)?;
+        Ok(Followed::Appended(index))
+    }
+
+    // Add the lines `reader` has to the end, it starts reading where the
+    // complete lines end.
+    fn read_lines<R: BufRead>(&mut self, reader: &mut LineReader<R>, seekable: bool, query: Option<&Query>) -> io::Result<()> {
+        let start = self.complete_bytes;
+        loop {
+            let offset = start + reader.bytes_read();
+            let matched = match reader.next_line()? {
+                Some((_, line)) => query.is_some_and(|query| !query.highlights(&String::from_utf8_lossy(line)).is_empty()),
+                None => break,
+            };
+            self.lines += 1;
+            // the first line past the last checkpoint starts the next one
+            if seekable && self.checkpoints.len() * CHECKPOINT < self.lines {
+                self.checkpoints.push(offset);
+            }
+            if matched {
+                self.matches.push(self.lines);
+            }
+            // without a line ending the line is still being written
+            if reader.line_ended() {
+                self.complete_lines = self.lines;
+                self.complete_bytes = start + reader.bytes_read();
+            }
+        }
+        self.bytes = start + reader.bytes_read();
+        if seekable {
+            // it may have grown while it was being read
+            self.file_len = self.file_len.max(self.bytes);
+        }
+        Ok(())
+    }
+
+    // Up to `count` lines starting at line `first`, with the query highlighted.
+    pub fn read(&self, first: usize, count: usize, query: Option<&Query>) -> Result<Vec<Snippet>, FileError> {
+        let first = first.max(1);
+        let (mut reader, mut line_number): (LineReader<Box<dyn BufRead + Send>>, usize) =
+            match self.checkpoints.get((first - 1) / CHECKPOINT) {
+                Some(&offset) => {
+                    let mut file = File::open(&self.path)?;
+                    file.seek(SeekFrom::Start(offset))?;
+                    let checkpoint = (first - 1) / CHECKPOINT * CHECKPOINT;
+                    (LineReader::new(Box::new(BufReader::new(file))), checkpoint)
+                }
+                None => (LineReader::open(&self.path)?, 0),
+            };
+        let mut lines = Vec::with_capacity(count);
+        // lines written since the file was indexed are left for the next `follow`
+        while lines.len() < count && line_number < self.lines {
+            let line = match reader.next_line()? {
+                Some((_, line)) => line,
+                None => break,
+            };
+            line_number += 1;
+            if line_number >= first {
+                lines.push(Snippet::new(line_number, &String::from_utf8_lossy(line), query));
+            }
+        }
+        Ok(lines)
+    }
+
+    // the first match after line `line`
+    pub fn next_match(&self, line: usize) -> Option<usize> {
+        let i = self.matches.partition_point(|&m| m <= line);
+        self.matches.get(i).copied()
+    }
+
+    // the last match before line `line`
+    pub fn previous_match(&self, line: usize) -> Option<usize> {
+        let i = self.matches.partition_point(|&m| m < line);
+        i.checked_sub(1).map(|i| self.matches[i])
+    }
+
+    // which match `line` is, counting from 1, if it is one
+    pub fn match_number(&self, line: usize) -> Option<usize> {
+        self.matches.binary_search(&line).ok().map(|i| i + 1)
+    }
+}
+
+// Tells files apart even when one is renamed away and another takes its path.
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::process;
+
+    fn temp_file(name: &str, text: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("multi-threaded_search-{}-{}.log", name, process::id()));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn append(path: &Path, text: &str) {
+        OpenOptions::new().append(true).open(path).unwrap().write_all(text.as_bytes()).unwrap();
+    }
+
+    fn texts(index: &LineIndex, first: usize, count: usize) -> Vec<(usize, String)> {
+        index
+            .read(first, count, None)
+            .unwrap()
+            .into_iter()
+            .map(|snippet| (snippet.line_number, snippet.text))
+            .collect()
+    }
+
+    #[test]
+    fn reads_any_lines_from_the_checkpoint_before_them() {
+        let text: String = (1..=2500)
+            .map(|i| if i % 700 == 0 { format!("line {} timeout\n", i) } else { format!("line {}\n", i) })
+            .collect();
+        let path = temp_file("checkpoints", &text);
+        let query = Query::parse("timeout").unwrap();
+        let index = LineIndex::build(&path, Some(&query)).unwrap();
+        assert_eq!((index.lines, index.bytes), (2500, text.len() as u64));
+        assert_eq!(index.checkpoints.len(), 3);
+        assert_eq!(texts(&index, 1000, 3), [(1000, "line 1000".to_string()), (1001, "line 1001".to_string()), (1002, "line 1002".to_string())]);
+        assert_eq!(texts(&index, 2499, 10), [(2499, "line 2499".to_string()), (2500, "line 2500".to_string())]);
+        assert_eq!(texts(&index, 0, 1), [(1, "line 1".to_string())]);
+        assert_eq!(index.matches, [700, 1400, 2100]);
+        assert_eq!((index.next_match(700), index.previous_match(700), index.match_number(1400)), (Some(1400), None, Some(2)));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn follows_lines_added_to_the_end() {
+        let path = temp_file("appended", "one\ntwo\n");
+        let index = LineIndex::build(&path, None).unwrap();
+        assert!(matches!(index.follow(None), Ok(Followed::Unchanged)));
+        // a line still being written is counted, and read again once it's finished
+        append(&path, "thr");
+        let index = match index.follow(None).unwrap() {
+            Followed::Appended(index) => index,
+            followed => panic!("{:?}", followed),
+        };
+        assert_eq!(index.lines, 3);
+        append(&path, "ee\nfour\n");
+        let index = match index.follow(None).unwrap() {
+            Followed::Appended(index) => index,
+            followed => panic!("{:?}", followed),
+        };
+        assert_eq!(index.lines, 4);
+        assert_eq!(texts(&index, 3, 2), [(3, "three".to_string()), (4, "four".to_string())]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn truncated_files_are_read_again() {
+        let path = temp_file("truncated", "one\ntwo\nthree\n");
+        let index = LineIndex::build(&path, None).unwrap();
+        fs::write(&path, "new\n").unwrap();
+        match index.follow(None).unwrap() {
+            Followed::Replaced(index) => assert_eq!(texts(&index, 1, 5), [(1, "new".to_string())]),
+            followed => panic!("{:?}", followed),
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    // only told apart by their inode, see `file_id`
+    #[cfg(unix)]
+    #[test]
+    fn rotated_files_are_read_again() {
+        let path = temp_file("rotated", "one\ntwo\n");
+        let index = LineIndex::build(&path, None).unwrap();
+        // moved away and a new file written in its place, even a bigger one
+        let rotated = path.with_extension("log.1");
+        fs::rename(&path, &rotated).unwrap();
+        fs::write(&path, "after rotating\nsecond\n").unwrap();
+        match index.follow(None).unwrap() {
+            Followed::Replaced(index) => assert_eq!(index.lines, 2),
+            followed => panic!("{:?}", followed),
+        }
+        // and gone for a moment in between
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(index.follow(None), Err(FileError::NotFound)));
+        fs::remove_file(rotated).unwrap();
+    }
+}
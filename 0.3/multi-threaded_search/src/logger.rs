@@ -1,22 +1,342 @@
-use std::path::Path;
-use std::fs;
-
-use crate::{Log, LOGS};
-
-pub async fn search_files<'a>(
-    vals: Vec<String>,
-    file_name: String,
-) -> (Vec<String>, Option<Log>) {
-    let test_string = fs::read_to_string(Path::new(&format!("{}/{}", LOGS, file_name)))
-        .unwrap()
-        .to_lowercase();
-    if vals.iter().all(|val| test_string.contains(val)) {
-        // notice how we also create the log file in this separate thread?
-        // It is not a huge improvement, because the `Log` type is very simple
-        // but this is more performant than say sending back the file name and making
-        // the main thread then create the `Log`
-        (vals, Some(Log::new(file_name)))
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::files::{FileInfo, LogFile};
+use crate::filter::Filter;
+use crate::highlight::{Snippet, SNIPPET_LINES};
+use crate::matcher::{index_of, Hits, Matcher};
+use crate::query::Query;
+use crate::scan::{FileError, LineReader};
+use crate::stats::FileStats;
+
+// a file stops being searched once this many matching lines have been found in it
+pub const LINE_MAX: usize = 1000;
+
+// Whether a search lists the files that match, or every line that matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mode {
+    Files,
+    Lines,
+}
+
+// What the query is matched against in `Mode::Files`. Matching lines are always
+// lines of the contents, so in `Mode::Lines` it is the contents whatever this is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Target {
+    #[default]
+    Contents,
+    // the path under the root, the file isn't opened at all
+    Names,
+    // the path counts as one more line, so `payments AND timeout` finds
+    // the files named payments with a timeout in them
+    Both,
+}
+
+// What searching one file turned up.
+#[derive(Debug, Clone)]
+pub enum Found {
+    File(FileMatch),
+    Lines(Vec<LineMatch>),
+    // the file couldn't be searched, it is neither a match nor known not to be one
+    Skipped(Skipped),
+}
+
+// A file that matches the query as a whole, used in `Mode::Files`.
+#[derive(Debug, Clone)]
+pub struct FileMatch {
+    pub file: LogFile,
+    pub info: FileInfo,
+    pub query: Arc<Query>,
+    // the first few matching lines
+    pub snippets: Vec<Snippet>,
+    pub hits: Hits,
+}
+
+// A single line that matches the query on its own, used in `Mode::Lines`.
+#[derive(Debug, Clone)]
+pub struct LineMatch {
+    pub file: LogFile,
+    pub snippet: Snippet,
+}
+
+#[derive(Debug, Clone)]
+pub struct Skipped {
+    pub file: LogFile,
+    pub error: FileError,
+}
+
+// One run of the search bars over the files, which are searched by `spawn_workers`.
+// Every change to the search starts a new generation and cancels the one before,
+// the file searches still in flight check `cancelled` so they stop right away,
+// and whatever they send back is dropped as its generation is no longer current.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub generation: u64,
+    pub query: Arc<Query>,
+    pub matcher: Arc<Matcher>,
+    pub mode: Mode,
+    // only the lines this lets through are searched, see `with_filter`
+    pub filter: Option<Arc<Filter>>,
+    pub target: Target,
+    // every file to be searched, shared with the worker threads
+    pub files: Arc<Vec<LogFile>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Search {
+    pub fn new(generation: u64, query: Query, mode: Mode, files: Vec<LogFile>) -> Self {
+        Search {
+            generation,
+            matcher: Arc::new(Matcher::new(&query)),
+            query: Arc::new(query),
+            mode,
+            filter: None,
+            target: Target::Contents,
+            files: Arc::new(files),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Search only the lines with the level and time `filter` asks for.
+    pub fn with_filter(mut self, filter: Option<Filter>) -> Self {
+        self.filter = filter.map(Arc::new);
+        self
+    }
+
+    // Match the query against the file names, or both them and the contents.
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    // The same search over other `files`, with a cancel of its own so it still runs
+    // once this one was stopped for having found enough, e.g. for files that changed since.
+    pub fn over(&self, files: Vec<LogFile>) -> Search {
+        Search {
+            files: Arc::new(files),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            ..self.clone()
+        }
+    }
+
+    // whether the files have to be read, the names alone are there without opening them
+    fn reads_contents(&self) -> bool {
+        self.mode == Mode::Lines || self.target != Target::Names
+    }
+
+    // whether a line is searched at all
+    fn allows(&self, line: &[u8]) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.allows(line))
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// Search every file in `search.files` on `threads` worker threads, each result being
+// handed to `send` on the thread that found it. The workers take the next file from a
+// shared counter rather than being handed a share up front, so a few big files don't
+// leave threads idle. A worker stops once `send` returns false, the search is cancelled
+// or there are no files left, the last one to stop drops the last copy of `send`.
+pub fn spawn_workers<F>(search: &Search, threads: usize, send: F)
+where
+    F: Fn(&LogFile, Option<Found>, FileStats) -> bool + Clone + Send + 'static,
+{
+    let next = Arc::new(AtomicUsize::new(0));
+    for _ in 0..threads.max(1).min(search.files.len()) {
+        let search = search.clone();
+        let send = send.clone();
+        let next = next.clone();
+        thread::spawn(move || {
+            while !search.is_cancelled() {
+                let file = match search.files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    Some(file) => file,
+                    None => break,
+                };
+                let (found, stats) = search_file(&search, file);
+                if !send(file, found, stats) {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+// Search a single file, this runs on one of the worker threads.
+// The file is read a line at a time, so it is never in memory all at once.
+// Alongside what was found comes how long it took and how much was read.
+// A file that can't be searched is `Found::Skipped` with the reason why.
+pub fn search_file(search: &Search, file: &LogFile) -> (Option<Found>, FileStats) {
+    let start = Instant::now();
+    let mut bytes = 0;
+    if !search.reads_contents() {
+        let stats = FileStats {
+            bytes,
+            time: start.elapsed(),
+            error: None,
+        };
+        return (search_name(search, file), stats);
+    }
+    let found = LineReader::open(&file.path()).map_err(FileError::from).and_then(|mut lines| {
+        if lines.is_binary()? {
+            return Err(FileError::Binary);
+        }
+        let found = search_lines(search, file, &mut lines);
+        bytes = lines.bytes_read();
+        Ok(found?)
+    });
+    let (found, error) = match found {
+        Ok(found) => (found, None),
+        Err(error) => (
+            Some(Found::Skipped(Skipped {
+                file: file.clone(),
+                error: error.clone(),
+            })),
+            Some(error),
+        ),
+    };
+    let stats = FileStats {
+        bytes,
+        time: start.elapsed(),
+        error,
+    };
+    (found, stats)
+}
+
+fn search_lines<R: BufRead>(search: &Search, file: &LogFile, lines: &mut LineReader<R>) -> io::Result<Option<Found>> {
+    if search.is_cancelled() {
+        return Ok(None);
+    }
+    match search.mode {
+        Mode::Files => {
+            let mut snippets = Vec::new();
+            let mut counts = vec![0; search.matcher.len()];
+            if search.target == Target::Both {
+                search.matcher.count(file.title().as_bytes(), &mut counts);
+            }
+            if scan_file(search, lines, &mut snippets, &mut counts)? == Some(true) {
+                // Finding the matching lines to show under the result is done here too,
+                // so the main thread is never stuck looking through a file
+                Ok(Some(Found::File(FileMatch {
+                    file: file.clone(),
+                    info: FileInfo::read(&file.path()),
+                    query: search.query.clone(),
+                    snippets,
+                    hits: search.matcher.hits(&counts),
+                })))
+            } else {
+                Ok(None)
+            }
+        }
+        // every line is matched against the whole query on its own
+        Mode::Lines => {
+            let mut found = Vec::new();
+            while let Some((line_number, line)) = lines.next_line()? {
+                if search.is_cancelled() {
+                    return Ok(None);
+                }
+                if search.allows(line) && search.matcher.matches(&search.query, line) {
+                    found.push(LineMatch {
+                        file: file.clone(),
+                        snippet: Snippet::new(line_number, &String::from_utf8_lossy(line), Some(&search.query)),
+                    });
+                    if found.len() == LINE_MAX {
+                        break;
+                    }
+                }
+            }
+            if found.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(Found::Lines(found)))
+            }
+        }
+    }
+}
+
+// Whether the path under the root on its own matches, for `Target::Names`.
+fn search_name(search: &Search, file: &LogFile) -> Option<Found> {
+    if search.is_cancelled() {
+        return None;
+    }
+    let mut counts = vec![0; search.matcher.len()];
+    let name = file.title();
+    search.matcher.count(name.as_bytes(), &mut counts);
+    let terms = search.query.terms();
+    let matched = search
+        .query
+        .decide(&|term| Some(counts[index_of(&terms, term)] > 0))
+        .expect("every term is known");
+    if matched {
+        Some(Found::File(FileMatch {
+            file: file.clone(),
+            info: FileInfo::read(&file.path()),
+            query: search.query.clone(),
+            snippets: Vec::new(),
+            hits: search.matcher.hits(&counts),
+        }))
     } else {
-        (vals, None)
+        None
+    }
+}
+
+// Whether the file as a whole matches the query, counting the hits of every term
+// and collecting snippets along the way, all in a single pass over the file.
+// Stops reading as soon as the file is known not to match, one that does is read
+// to the end so the hit counts are of the whole file.
+// With a filter only the lines it lets through count, and at least one has to.
+// `None` if the search was cancelled.
+fn scan_file<R: BufRead>(
+    search: &Search,
+    lines: &mut LineReader<R>,
+    snippets: &mut Vec<Snippet>,
+    counts: &mut [usize],
+) -> io::Result<Option<bool>> {
+    let terms = search.query.terms();
+    // the terms already seen in the name may be enough to decide it
+    let mut decided = search
+        .query
+        .decide(&|term| if counts[index_of(&terms, term)] > 0 { Some(true) } else { None })
+        .filter(|_| counts.iter().any(|&count| count > 0));
+    if decided == Some(false) {
+        return Ok(decided);
+    }
+    let mut allowed = search.filter.is_none();
+    while let Some((line_number, line)) = lines.next_line()? {
+        if search.is_cancelled() {
+            return Ok(None);
+        }
+        if !search.allows(line) {
+            continue;
+        }
+        allowed = true;
+        // a search on the filter alone has no terms, every line it lets through is a match
+        let hit = search.matcher.count(line, counts) || terms.is_empty();
+        if hit && snippets.len() < SNIPPET_LINES {
+            snippets.extend(Snippet::preview(line_number, &String::from_utf8_lossy(line), &search.query));
+        }
+        if hit && decided.is_none() {
+            // a term that was seen can't be unseen, one that wasn't might still be further on
+            decided = search
+                .query
+                .decide(&|term| if counts[index_of(&terms, term)] > 0 { Some(true) } else { None });
+            if decided == Some(false) {
+                return Ok(decided);
+            }
+        }
+    }
+    if !allowed {
+        return Ok(Some(false));
     }
-}
\ No newline at end of file
+    // at the end of the file every term that wasn't seen is known not to be there
+    Ok(decided.or_else(|| search.query.decide(&|term| Some(counts[index_of(&terms, term)] > 0))))
+}
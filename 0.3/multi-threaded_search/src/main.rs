@@ -1,35 +1,158 @@
-use iced::{Scrollable, scrollable, Application, Container, Text, Element, Column, Command, Settings, Length, HorizontalAlignment, Clipboard, Font, Row, Button, Space, button, TextInput, text_input};
-use std::path::Path;
-use std::fs;
-use std::cmp::min;
-use lipsum::{lipsum, lipsum_title};
-use std::time::{SystemTime, UNIX_EPOCH};
+use iced::keyboard::{self, KeyCode};
+use iced_native::{event, subscription};
+use iced::{Scrollable, scrollable, Rectangle, Application, Subscription, Radio, Container, Text, Element, Column, Command, Settings, Length, HorizontalAlignment, Clipboard, Font, Row, Button, Space, button, TextInput, text_input, Checkbox, Color, PickList, pick_list, ProgressBar};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 
-mod logger;
-use logger::search_files;
+use multi_threaded_search::logger::{Search, Mode, Target, Found, FileMatch, LineMatch, Skipped, LINE_MAX};
+use multi_threaded_search::query::Query;
+use multi_threaded_search::index::{Index, IndexStatus};
+use multi_threaded_search::files::{list_files, files_at, FileInfo, LogFile, Selection};
+use multi_threaded_search::highlight::Snippet;
+use multi_threaded_search::matcher::Hits;
+use multi_threaded_search::fold::Folding;
+use multi_threaded_search::stats::SearchStats;
+use multi_threaded_search::scan::Compression;
+use multi_threaded_search::generate::Corpus;
+
+mod watcher;
+use watcher::FileChange;
+
+mod args;
+use args::{ArgsError, Flags, USAGE};
+
+mod pool;
+use pool::Batch;
+
+mod cli;
+
+mod filter_bar;
+use filter_bar::{FilterBar, FilterMessage};
+
+mod file_bar;
+use file_bar::{FileBar, FileMessage};
+
+mod export;
+use export::{export, ExportFormat, Exported};
+
+mod saved;
+use saved::{Config, SavedBar, SavedSearch};
+
+mod generator;
+use generator::{Generator, GeneratorMessage};
+
+mod viewer;
+use viewer::{Viewer, ViewerMessage};
+
+mod sort;
+use sort::{groups, Sort};
 
-pub const LOGS: &str = "./logs";
 const LOG_MAX: usize = 100;
+// matching lines are shown in pages of this many
+const LINES_PER_PAGE: usize = 100;
+const ERROR_COLOR: Color = Color::from_rgb(0.8, 0.2, 0.2);
+const ROOT_COLOR: Color = Color::from_rgb(0.5, 0.5, 0.5);
+const LINE_NUMBER_COLOR: Color = Color::from_rgb(0.5, 0.5, 0.5);
+const HIGHLIGHT_COLOR: Color = Color::from_rgb(0.85, 0.4, 0.0);
+const HITS_COLOR: Color = Color::from_rgb(0.3, 0.5, 0.8);
+// about how tall a line of `lines_view` is and a directory's header when grouping, see `State::move_cursor`
+const LINE_HEIGHT: f32 = 18.0;
+const HEADER_HEIGHT: f32 = 40.0;
 
 enum App {
     Loading,
-    Loaded(State)
+    // boxed, it is far bigger than `Loading`
+    Loaded(Box<State>)
 }
 
 struct State {
     scroll: scrollable::State,
+    roots: Vec<PathBuf>,
     logs: Vec<Log>,
-    unsearched_files: Vec<String>,
+    // the results in `Mode::Lines`, shown a page at a time
+    lines: Vec<LineMatch>,
+    mode: Mode,
+    folding: Folding,
+    page: usize,
+    prev_page_button: button::State,
+    next_page_button: button::State,
+    // files the search couldn't look through, listed under the results
+    skipped: Vec<Skipped>,
+    skipped_open: bool,
+    skipped_button: button::State,
+    // the order the results are in, and whether they are put under their directory
+    sort: Sort,
+    sorts: pick_list::State<Sort>,
+    grouped: bool,
+    // the directories whose results are folded away while grouping
+    collapsed: HashSet<PathBuf>,
+    group_buttons: Vec<button::State>,
+    // the result the up and down keys are on, kept by file as results move when others are found
+    cursor: Option<LogFile>,
+    // whether results were left out for not being in the first `LOG_MAX` of the sort,
+    // sorting differently then means looking through the files again
+    trimmed: bool,
+    // how many files the running search has left, the search is done at 0
+    unsearched: usize,
+    // files that changed since the search went through them, searched again once it is done
+    changed: Vec<LogFile>,
+    // the changed files being searched again on the pool, and how many of them are left
+    rechecks: Option<Search>,
+    rechecking: usize,
+    // counts the rechecks so each gets a pool of its own, see `pool::run`
+    pass: u64,
+    threads: usize,
     search_bars: Vec<SearchBar>,
+    filter_bar: FilterBar,
+    file_bar: FileBar,
+    // the files that are listed and searched, from `file_bar` when they were last listed
+    selection: Selection,
+    // the search that is running or finished, `None` when every file is listed
+    search: Option<Search>,
+    generation: u64,
     create_button: button::State,
-    num_to_create: u8,
-    search_start: Option<SystemTime>,
-    speed_text: String,
+    generator: Generator,
+    // how many test files have been written out of how many, both 0 when none are being made
+    created: usize,
+    to_create: usize,
+    // the numbers for the running search, or the last one once it's done
+    stats: Option<SearchStats>,
+    // `None` while the index is being (re)built, searches just check every file until it's back
+    // shared with the searches that are listing their files
+    index: Option<Arc<Index>>,
+    index_text: String,
+    index_button: button::State,
+    // the search history and saved searches, written back whenever they change
+    config: Config,
+    // the last problem reading or writing `config`
+    config_error: Option<String>,
+    save_name: String,
+    save_name_state: text_input::State,
+    save_button: button::State,
+    // the saved search that was last opened or saved, the one `delete_button` removes
+    saved_name: Option<String>,
+    saved_list: pick_list::State<String>,
+    delete_button: button::State,
+    export_format: ExportFormat,
+    export_formats: pick_list::State<ExportFormat>,
+    export_path: String,
+    export_path_state: text_input::State,
+    export_button: button::State,
+    exporting: bool,
+    // how the last export went
+    export_status: Option<Result<String, String>>,
 }
 
 #[derive(Debug, Clone)]
 struct LoadState {
-    // fill with async loaded things
+    roots: Vec<PathBuf>,
+    threads: usize,
+    index: Index,
+    index_status: IndexStatus,
+    config: Config,
+    config_error: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,27 +163,75 @@ enum LoadError {
 #[derive(Debug, Clone)]
 enum Message {
     Loaded(Result<LoadState, LoadError>),
-    Log(usize, LogMessage),
+    // by file rather than position, results found later can be put in front of it
+    Log(LogFile, LogMessage),
     SearchChanged(usize, SearchBarMessage),
-    AddLog((Vec<String>, Option<Log>)),
+    FilterChanged(FilterMessage),
+    FilesPicked(FileMessage),
+    // a search with the files it goes through, once they are listed
+    Started(Search),
+    Searched(Batch),
+    SetMode(Mode),
+    SetFolding(Folding),
+    Page(usize),
+    ToggleSkipped,
+    SetSort(Sort),
+    SetGrouped(bool),
+    ToggleGroup(PathBuf),
+    // and whether a text input had the key, see `shortcut`
+    Shortcut(Shortcut, bool),
+    ToggleGenerator,
+    Generator(GeneratorMessage),
     MoarFiles,
-    MoarFiled(()),
-    GotSpeed(Option<(u128, u128, u64)>)
+    MoarFiled(Result<u64, String>),
+    RebuildIndex,
+    Indexed((Index, IndexStatus)),
+    FilesChanged(Vec<FileChange>),
+    // changed files searched again, see `State::recheck`
+    Rechecked(Batch),
+    // files listed without a search, with what is shown about them, for the generation they were listed in
+    Listed(u64, Vec<(LogFile, FileInfo)>),
+    SaveNameChanged(String),
+    SaveSearch,
+    OpenSaved(String),
+    DeleteSaved,
+    ConfigSaved(Result<(), String>),
+    SetExportFormat(ExportFormat),
+    ExportPathChanged(String),
+    Export,
+    Exported(Result<String, String>),
 }
 
 pub fn main() -> iced::Result {
-    App::run(Settings::default())
+    let flags = match Flags::parse(std::env::args().skip(1)) {
+        Ok(flags) => flags,
+        Err(ArgsError::Help) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Err(ArgsError::Invalid(e)) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Some(corpus) = &flags.generate {
+        std::process::exit(cli::generate(&flags, corpus));
+    }
+    if flags.searches() {
+        std::process::exit(cli::run(flags));
+    }
+    App::run(Settings::with_flags(flags))
 }
 
 impl Application for App {
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = ();
+    type Flags = Flags;
 
-    fn new(_flags: ()) -> (App, Command<Message>) {
+    fn new(flags: Flags) -> (App, Command<Message>) {
         (
             App::Loading,
-            Command::perform(LoadState::load(), Message::Loaded),
+            Command::perform(LoadState::load(flags), Message::Loaded),
         )
     }
 
@@ -74,147 +245,467 @@ impl Application for App {
                 match message {
                     // when async completes this is called & converts LoadState to State, which is then
                     // displayed using view()
-                    // `_LoadState' is not used here, but will likely be needed in your program.
-                    // remove the underscore and then the data in LoadState can be moved over to 
-                    Message::Loaded(Ok(_load_state)) => {
-                        *self = App::Loaded(State{
+                    Message::Loaded(Ok(load_state)) => {
+                        *self = App::Loaded(Box::new(State{
                             scroll: scrollable::State::new(),
+                            roots: load_state.roots,
                             logs: Vec::new(),
+                            lines: Vec::new(),
+                            mode: Mode::Files,
+                            folding: Folding::Case,
+                            page: 0,
+                            prev_page_button: button::State::new(),
+                            next_page_button: button::State::new(),
+                            skipped: Vec::new(),
+                            skipped_open: false,
+                            skipped_button: button::State::new(),
+                            sort: Sort::default(),
+                            sorts: pick_list::State::default(),
+                            grouped: false,
+                            collapsed: HashSet::new(),
+                            group_buttons: Vec::new(),
+                            cursor: None,
+                            trimmed: false,
                             search_bars: vec![SearchBar::new(0)],
-                            unsearched_files: Vec::new(),
+                            filter_bar: FilterBar::default(),
+                            file_bar: FileBar::default(),
+                            selection: Selection::default(),
+                            search: None,
+                            generation: 0,
+                            unsearched: 0,
+                            changed: Vec::new(),
+                            rechecks: None,
+                            rechecking: 0,
+                            pass: 0,
+                            threads: load_state.threads,
                             create_button: button::State::new(),
-                            num_to_create: 0,
-                            search_start: None,
-                            speed_text: "Create files and use the search below to feel the speed of Rust + Iced.".to_string(),
-                        })
+                            generator: Generator::default(),
+                            created: 0,
+                            to_create: 0,
+                            stats: None,
+                            index: Some(Arc::new(load_state.index)),
+                            index_text: load_state.index_status.text(),
+                            index_button: button::State::new(),
+                            config: load_state.config,
+                            config_error: load_state.config_error,
+                            save_name: String::new(),
+                            save_name_state: text_input::State::new(),
+                            save_button: button::State::new(),
+                            saved_name: None,
+                            saved_list: pick_list::State::default(),
+                            delete_button: button::State::new(),
+                            export_format: ExportFormat::Json,
+                            export_formats: pick_list::State::default(),
+                            export_path: "results.json".to_string(),
+                            export_path_state: text_input::State::new(),
+                            export_button: button::State::new(),
+                            exporting: false,
+                            export_status: None,
+                        }));
+                        if let App::Loaded(state) = self {
+                            return state.update_logs();
+                        }
+                        Command::none()
                     },
-                    _ => ()
+                    _ => Command::none()
                 }
-                Command::none()
             }
             App::Loaded(state) => {
                 match message {
-                    Message::AddLog((vals, log)) => {
-                        if state.logs.len() <= LOG_MAX
-                            && vals
-                                .iter()
-                                .zip(state.search_bars.iter().fold(
-                                    Vec::with_capacity(state.search_bars.len()),
-                                    |mut v, bar| {
-                                        v.push(&bar.value);
-                                        v
-                                    },
-                                ))
-                                .all(|(a, b)| a == &b.to_lowercase())
-                        {
-                            if let Some(log) = log {
-                                state.logs.push(log);
+                    Message::Started(search) if search.generation != state.generation => Command::none(),
+                    Message::Started(search) => {
+                        state.unsearched = search.files.len();
+                        let mut stats = SearchStats::new();
+                        if search.files.is_empty() {
+                            stats.finish(0);
+                        }
+                        state.stats = Some(stats);
+                        state.search = Some(search);
+                        Command::none()
+                    }
+                    // results from a search that has since been replaced are dropped
+                    Message::Searched(batch) if batch.generation != state.generation => Command::none(),
+                    Message::Searched(batch) => {
+                        state.unsearched = state.unsearched.saturating_sub(batch.stats.len());
+                        for found in batch.found {
+                            state.add_found(found);
+                        }
+                        let remaining = state.unsearched;
+                        if state.is_full() {
+                            // enough results, setting this to 0 drops the pool's subscription
+                            // and cancelling stops the workers in the middle of the file they are on
+                            state.unsearched = 0;
+                            if let Some(search) = &state.search {
+                                search.cancel();
                             }
-                            if state.unsearched_files.len() > 0 {
-                                Command::perform(
-                                    search_files(vals, state.unsearched_files.remove(0)),
-                                    Message::AddLog,
-                                )
-                            } else {
-                            if let Some(start_time) = state.search_start {
-                                Command::perform(calc_speed(0, start_time), Message::GotSpeed)
-                            } else {Command::none()}
+                        }
+                        if let Some(stats) = &mut state.stats {
+                            for file in &batch.stats {
+                                stats.add(file);
+                            }
+                            if state.unsearched == 0 {
+                                stats.finish(remaining);
                             }
+                        }
+                        state.recheck();
+                        Command::none()
+                    }
+                    Message::Rechecked(batch) if batch.generation != state.generation => Command::none(),
+                    Message::Rechecked(batch) => {
+                        state.rechecking = state.rechecking.saturating_sub(batch.stats.len());
+                        for found in batch.found {
+                            state.add_found(found);
+                        }
+                        // it doesn't match any more, or it's readable again and never did
+                        for file in batch.missed {
+                            state.logs.retain(|log| log.is_following() || log.file != file);
+                            state.lines.retain(|line| line.file != file);
+                            state.skipped.retain(|skipped| skipped.file != file);
+                        }
+                        if state.rechecking == 0 {
+                            // the files that changed while these were searched are next
+                            state.rechecks = None;
+                            state.recheck();
+                        }
+                        Command::none()
+                    }
+                    Message::SearchChanged(i, SearchBarMessage::ToggleRegex(regex)) => {
+                        state.search_bars[i].regex = regex;
+                        if state.search_bars[i].value.is_empty() {
+                            // nothing to search for yet, the toggle is used once text is entered
+                            state.search_bars[i].error = None;
+                            Command::none()
                         } else {
-                            let length = state.unsearched_files.len();
-                            state.unsearched_files.clear();
-                            if let Some(start_time) = state.search_start {
-                                Command::perform(calc_speed(length, start_time), Message::GotSpeed)
-                            } else {Command::none()}
+                            state.search()
                         }
                     }
+                    // only searches that were kept are worth remembering, not every key pressed
+                    Message::SearchChanged(i, SearchBarMessage::Submitted) => {
+                        state.search_bars[i].suggest = false;
+                        state.remember()
+                    }
+                    Message::SearchChanged(i, SearchBarMessage::Suggestion(val)) => {
+                        let bar = &mut state.search_bars[i];
+                        bar.value = val;
+                        bar.suggest = false;
+                        bar.state.move_cursor_to_end();
+                        if state.search_bars.len() - 1 == i {
+                            state.search_bars.push(SearchBar::new(i + 1));
+                        }
+                        Command::batch(vec![state.search(), state.remember()])
+                    }
                     Message::SearchChanged(i, SearchBarMessage::InputChanged(val)) => {
-                        // run search as multithreaded Commands to speed up search
+                        // the search itself runs on the worker pool, see `subscription`
                         // update bar and add new if necessary
-                        state.search_start = Some(SystemTime::now());
                         state.search_bars[i].value = val.clone();
+                        state.search_bars[i].suggest = true;
                         if state.search_bars.len() - 1 == i {
                             state.search_bars.push(SearchBar::new(i + 1));
                         }
                         // remove empty search bars
-                        if val == "".to_string() {
+                        if val.is_empty() {
                             if i == 0 {
-                                state.update_logs();
-                                return Command::none();
+                                state.search_bars = vec![SearchBar::new(0)];
+                                return state.refresh();
                             } else {
                                 state.search_bars.remove(i);
                                 for i in 0..state.search_bars.len() {
                                     state.search_bars[i].num = i;
                                     state.search_bars[i].message = if i == 0 {
-                                        "Search".to_string()
+                                        "Search, e.g. timeout AND NOT retry".to_string()
                                     } else {
                                         format!("Search term {} (Optional)", i + 1)
                                     };
                                 }
                             }
                         }
-                        if state.search_bars.len() == 1 && state.search_bars[0].value == "".to_string() {
-                            state.update_logs();
-                            Command::none()
-                        } else {
-                            state.logs = Vec::with_capacity(LOG_MAX);
-                            state.unsearched_files = if let Ok(files) = fs::read_dir(Path::new(LOGS)) {
-                                files.fold(
-                                    Vec::with_capacity(LOG_MAX),
-                                    |mut v, file| {
-                                        v.push(file.unwrap().file_name().to_string_lossy().to_string());
-                                        v
-                                    },
-                                )
-                            } else {Vec::new()};
-                            // Note: limit to 15 active search threads as limit on windows
-                            Command::batch((0..min(15, state.unsearched_files.len())).into_iter().fold(
-                                Vec::with_capacity(15),
-                                |mut v, _i| {
-                                    v.push(Command::perform(
-                                        search_files(
-                                            state.search_bars.iter().fold(
-                                                Vec::with_capacity(state.search_bars.len()),
-                                                |mut v, bar| {
-                                                    v.push(bar.value.to_lowercase().clone());
-                                                    v
-                                                },
-                                            ),
-                                            state.unsearched_files.remove(0),
-                                        ),
-                                        Message::AddLog,
-                                    ));
-                                    v
-                                },
-                            ))
+                        state.refresh()
+                    }
+                    Message::FilterChanged(msg) => {
+                        state.filter_bar.update(msg);
+                        state.refresh()
+                    }
+                    Message::FilesPicked(msg) => {
+                        state.file_bar.update(msg);
+                        state.refresh()
+                    }
+                    Message::Log(file, msg) => state.log_message(file, msg),
+                    Message::SaveNameChanged(name) => {
+                        state.save_name = name;
+                        Command::none()
+                    }
+                    // enter in the name field gets here without the button's checks
+                    Message::SaveSearch if !state.can_save() => Command::none(),
+                    Message::SaveSearch => {
+                        let name = state.save_name.trim().to_string();
+                        state.config.save_search(SavedSearch {
+                            name: name.clone(),
+                            bars: state
+                                .search_bars
+                                .iter()
+                                .filter(|bar| !bar.value.trim().is_empty())
+                                .map(|bar| SavedBar {
+                                    value: bar.value.clone(),
+                                    regex: bar.regex,
+                                })
+                                .collect(),
+                            mode: state.mode,
+                            folding: state.folding,
+                            filter: state.filter_bar.saved(),
+                            files: state.file_bar.saved(),
+                        });
+                        state.saved_name = Some(name);
+                        state.save_name = String::new();
+                        state.save_config()
+                    }
+                    Message::OpenSaved(name) => {
+                        if let Some(saved) = state.config.get(&name).cloned() {
+                            state.saved_name = Some(name);
+                            return state.open_saved(saved);
                         }
+                        Command::none()
+                    }
+                    Message::DeleteSaved => match state.saved_name.take() {
+                        Some(name) => {
+                            state.config.delete(&name);
+                            state.save_config()
+                        }
+                        None => Command::none(),
+                    },
+                    Message::ConfigSaved(result) => {
+                        state.config_error = result.err();
+                        Command::none()
+                    }
+                    Message::SetExportFormat(format) => {
+                        state.export_format = format;
+                        state.export_path = format.with_extension(&state.export_path);
+                        Command::none()
                     }
-                    Message::Log(i, msg) => {
-                        state.logs[i].update(msg);
+                    Message::ExportPathChanged(path) => {
+                        state.export_path = path;
+                        Command::none()
+                    }
+                    Message::Export if !state.can_export() => Command::none(),
+                    Message::Export => {
+                        // everything found so far, not just what is on screen
+                        let files = match state.mode {
+                            Mode::Files => state
+                                .logs
+                                .iter()
+                                .map(|log| Exported {
+                                    path: log.file.path(),
+                                    matches: log.hits.total(),
+                                    hits: log.hits.clone(),
+                                    lines: log.snippets.clone(),
+                                })
+                                .collect(),
+                            Mode::Lines => Exported::from_lines(&state.lines),
+                        };
+                        state.exporting = true;
+                        Command::perform(
+                            export(PathBuf::from(state.export_path.trim()), state.export_format, files),
+                            Message::Exported,
+                        )
+                    }
+                    Message::Exported(result) => {
+                        state.exporting = false;
+                        state.export_status = Some(result);
+                        Command::none()
+                    }
+                    Message::ToggleGenerator => {
+                        state.generator.open = !state.generator.open;
+                        Command::none()
+                    }
+                    Message::Generator(message) => {
+                        state.generator.update(message);
                         Command::none()
                     }
                     Message::MoarFiles => {
-                        state.num_to_create = 100;
-                        Command::batch
-                            ((0..100)
-                                .into_iter()
-                                .fold(Vec::with_capacity(100), |mut vec, _i| {
-                                vec.push(Command::perform(
-                                        create_file(), Message::MoarFiled
-                                    ));
-                                vec
-                                }))
-                    }
-                    Message::MoarFiled(_) => {
-                        state.num_to_create -= 1;
+                        let corpus = match state.generator.corpus() {
+                            Ok(corpus) => Arc::new(corpus),
+                            Err(error) => {
+                                state.generator.error = Some(error);
+                                return Command::none();
+                            }
+                        };
+                        state.generator.error = None;
+                        if state.to_create > 0 || corpus.files == 0 {
+                            return Command::none();
+                        }
+                        state.created = 0;
+                        state.to_create = corpus.files;
+                        // every file only depends on the seed and its number, so they can be written in any order
+                        Command::batch((0..corpus.files).map(|i| {
+                            Command::perform(
+                                create_file(state.roots[0].clone(), corpus.clone(), i),
+                                Message::MoarFiled,
+                            )
+                        }))
+                    }
+                    Message::MoarFiled(result) => {
+                        state.created += 1;
+                        if let (Err(error), None) = (result, &state.generator.error) {
+                            state.generator.error = Some(error);
+                        }
+                        if state.created < state.to_create {
+                            return Command::none();
+                        }
+                        state.created = 0;
+                        state.to_create = 0;
+                        // add the new files to the index once they are all written
+                        match state.index.take() {
+                            Some(index) => {
+                                state.index_text = "Updating index...".to_string();
+                                // a search that is still listing its files keeps the old one
+                                let index = Arc::try_unwrap(index).unwrap_or_else(|index| (*index).clone());
+                                Command::perform(index.update(state.roots.clone()), Message::Indexed)
+                            }
+                            None => Command::none(),
+                        }
+                    }
+                    Message::RebuildIndex => {
+                        state.index = None;
+                        state.index_text = "Rebuilding index...".to_string();
+                        Command::perform(Index::rebuild(state.roots.clone()), Message::Indexed)
+                    }
+                    Message::Indexed((index, status)) => {
+                        state.index = Some(Arc::new(index));
+                        state.index_text = status.text();
+                        Command::none()
+                    }
+                    Message::FilesChanged(changes) => {
+                        // only the files that changed are searched again, not the whole directory
+                        let mut commands = Vec::new();
+                        let mut unlisted = Vec::new();
+                        for change in changes {
+                            let changed = change.path().to_path_buf();
+                            match change {
+                                // this might be a whole directory, so drop everything under it
+                                FileChange::Removed(path) => {
+                                    state.logs.retain(|log| log.is_following() || !log.file.path().starts_with(&path));
+                                    state.lines.retain(|line| !line.file.path().starts_with(&path));
+                                    state.skipped.retain(|skipped| !skipped.file.path().starts_with(&path));
+                                }
+                                FileChange::Changed(path) => {
+                                    for file in files_at(&state.roots, &path, &state.selection) {
+                                        match &state.search {
+                                            // searched again once the search is done with it, see `State::recheck`
+                                            Some(_) if !state.changed.contains(&file) => state.changed.push(file),
+                                            Some(_) => (),
+                                            // no search, every file is listed
+                                            None if state.mode == Mode::Files && !state.logs.iter().any(|log| log.file == file) => {
+                                                unlisted.push(file)
+                                            }
+                                            None => (),
+                                        }
+                                    }
+                                }
+                            }
+                            // followed logs catch up with their file themselves, even when it
+                            // is gone for a moment while being rotated
+                            for log in state.logs.iter_mut() {
+                                if log.is_following() && log.file.path().starts_with(&changed) {
+                                    let file = log.file.clone();
+                                    commands.push(
+                                        log.update(LogMessage::Viewer(ViewerMessage::FileChanged))
+                                            .map(move |msg| Message::Log(file.clone(), msg)),
+                                    );
+                                }
+                            }
+                        }
+                        state.recheck();
+                        if !unlisted.is_empty() {
+                            let generation = state.generation;
+                            commands.push(Command::perform(read_info(unlisted), move |files| {
+                                Message::Listed(generation, files)
+                            }));
+                        }
+                        Command::batch(commands)
+                    }
+                    // a search has started since, or the files were listed again
+                    Message::Listed(generation, _) if generation != state.generation || state.search.is_some() => {
                         Command::none()
                     }
-                    Message::GotSpeed(result) => {
-                        match result {
-                            Some(speed) if state.unsearched_files.len() == 0 => {
-                                state.speed_text = format!("{}ms, ~{}Mb/s ({} files total)", speed.0, speed.1, speed.2);
+                    Message::Listed(_, files) => {
+                        for (file, info) in files {
+                            if !state.logs.iter().any(|log| log.file == file) {
+                                state.insert_log(Log::new(file, info));
                             }
-                            _ => ()
+                        }
+                        Command::none()
+                    }
+                    Message::SetMode(mode) => {
+                        state.mode = mode;
+                        // the same query again, but looking for lines instead of files or back
+                        if state.search.is_some() {
+                            return state.search();
+                        }
+                        Command::none()
+                    }
+                    Message::SetFolding(folding) => {
+                        state.folding = folding;
+                        // the same query again, folded the new way
+                        if state.search.is_some() {
+                            return state.search();
+                        }
+                        Command::none()
+                    }
+                    Message::Page(page) => {
+                        state.page = page;
+                        Command::none()
+                    }
+                    Message::ToggleSkipped => {
+                        state.skipped_open = !state.skipped_open;
+                        Command::none()
+                    }
+                    Message::SetSort(sort) => {
+                        state.sort = sort;
+                        if state.trimmed {
+                            // the ones that were left out may come first now
+                            return state.refresh();
+                        }
+                        state.logs.sort_by(|a, b| sort.compare(a, b));
+                        Command::none()
+                    }
+                    Message::SetGrouped(grouped) => {
+                        state.grouped = grouped;
+                        Command::none()
+                    }
+                    Message::Shortcut(Shortcut::FocusSearch, _) => {
+                        state.focus_search_bar(0);
+                        Command::none()
+                    }
+                    Message::Shortcut(Shortcut::AddBar, _) => {
+                        // there is always an empty bar at the end for another term
+                        let last = state.search_bars.len() - 1;
+                        state.focus_search_bar(last);
+                        Command::none()
+                    }
+                    Message::Shortcut(Shortcut::Clear, _) => {
+                        state.search_bars = vec![SearchBar::new(0)];
+                        state.refresh()
+                    }
+                    // arrows typed into one of the other inputs are left to it
+                    Message::Shortcut(Shortcut::Up | Shortcut::Down, true)
+                        if !state.search_bars.iter().any(|bar| bar.state.is_focused()) =>
+                    {
+                        Command::none()
+                    }
+                    Message::Shortcut(direction @ (Shortcut::Up | Shortcut::Down), _) => {
+                        // off the search bar, so enter opens the result rather than searching again
+                        state.unfocus_inputs();
+                        state.move_cursor(direction == Shortcut::Down);
+                        Command::none()
+                    }
+                    Message::Shortcut(Shortcut::Toggle, _) => {
+                        match state.cursor.clone() {
+                            Some(file) => state.log_message(file, LogMessage::ToggleView),
+                            None => Command::none(),
+                        }
+                    }
+                    Message::ToggleGroup(dir) => {
+                        if !state.collapsed.remove(&dir) {
+                            state.collapsed.insert(dir);
                         }
                         Command::none()
                     }
@@ -224,68 +715,304 @@ impl Application for App {
 
         }
     }
-    fn view(&mut self) -> Element<Message> {
+    fn subscription(&self) -> Subscription<Message> {
+        match self {
+            App::Loading => Subscription::none(),
+            // keep the results up to date as files are written under the roots
+            App::Loaded(state) => {
+                let mut subscriptions = vec![
+                    watcher::watch(state.roots.clone()).map(Message::FilesChanged),
+                    subscription::events_with(shortcut),
+                ];
+                if let Some(search) = state.search.as_ref().filter(|_| state.unsearched > 0) {
+                    subscriptions.push(pool::run(search.clone(), 0, state.threads).map(Message::Searched));
+                }
+                if let Some(rechecks) = &state.rechecks {
+                    subscriptions.push(pool::run(rechecks.clone(), state.pass, state.threads).map(Message::Rechecked));
+                }
+                Subscription::batch(subscriptions)
+            }
+        }
+    }
+
+    fn view(&mut self) -> Element<'_, Message> {
         match self {
             App::Loading => loading_message(),
-            App::Loaded(State {
+            App::Loaded(state) => {
+                let (can_save, can_export) = (state.can_save(), state.can_export());
+                let State {
                     // list state variables to be accessable 
                     scroll,
                     logs,
-                    unsearched_files,
+                    unsearched,
                     search_bars,
+                    filter_bar,
+                    file_bar,
                     create_button,
-                    num_to_create,
-                    speed_text,
+                    generator,
+                    created,
+                    to_create,
+                    stats,
+                    index,
+                    index_text,
+                    index_button,
+                    roots,
+                    lines,
+                    mode,
+                    folding,
+                    page,
+                    prev_page_button,
+                    next_page_button,
+                    skipped,
+                    skipped_open,
+                    skipped_button,
+                    sort,
+                    sorts,
+                    grouped,
+                    collapsed,
+                    group_buttons,
+                    cursor,
+                    trimmed,
+                    search,
+                    config,
+                    config_error,
+                    save_name,
+                    save_name_state,
+                    save_button,
+                    saved_name,
+                    saved_list,
+                    delete_button,
+                    export_format,
+                    export_formats,
+                    export_path,
+                    export_path_state,
+                    export_button,
+                    exporting,
+                    export_status,
                     ..
-            }) => {
-        let logs = logs.iter_mut().take(LOG_MAX);
-        let logs_count = logs.len();
+                } = &mut **state;
+        // with several roots the same relative path can turn up more than once
+        let show_root = roots.len() > 1;
+        // a search that is still listing its files has no numbers yet
+        let searching = *unsearched > 0 || (search.is_some() && stats.is_none());
+        let generating = *to_create > 0;
         let speed_row = Row::new().spacing(50)
-                .push(if *num_to_create == 0 {
-                        Button::new(create_button, Text::new("Create 100 files (~70kb)")).on_press(Message::MoarFiles)
+                .push(Button::new(create_button, Text::new(if generator.open {"Hide file creation"} else {"Create files"}))
+                    .on_press(Message::ToggleGenerator))
+                .push(Text::new(&index_text[..]))
+                .push(if index.is_some() {
+                        Button::new(index_button, Text::new("Rebuild index")).on_press(Message::RebuildIndex)
                     } else {
-                        Button::new(create_button, Text::new("createing files"))
+                        Button::new(index_button, Text::new("Indexing"))
+                    });
+        let stats_row = match stats {
+            Some(stats) => stats.fields().into_iter().fold(
+                Row::new().spacing(20).push(Text::new(if stats.is_finished() {"Last search:"} else {"Searching:"})),
+                |row, (label, value)| {
+                    row.push(
+                        Row::new()
+                            .spacing(5)
+                            .push(Text::new(label).color(ROOT_COLOR))
+                            .push(Text::new(value)),
+                    )
+                },
+            ),
+            None => Row::new().push(Text::new("Create files and use the search below to feel the speed of Rust + Iced.")),
+        };
+        let mut save = Button::new(save_button, Text::new("Save search"));
+        if can_save {
+            save = save.on_press(Message::SaveSearch);
+        }
+        let mut delete = Button::new(delete_button, Text::new("Delete"));
+        if saved_name.is_some() {
+            delete = delete.on_press(Message::DeleteSaved);
+        }
+        let mut saved_row = Row::new()
+            .spacing(20)
+            .push(Text::new("Saved:"))
+            .push(PickList::new(saved_list, config.names(), saved_name.clone(), Message::OpenSaved))
+            .push(delete)
+            .push(
+                TextInput::new(save_name_state, "Name to save this search as", save_name, Message::SaveNameChanged)
+                    .on_submit(Message::SaveSearch)
+                    .padding(5),
+            )
+            .push(save);
+        if let Some(error) = config_error {
+            saved_row = saved_row.push(Text::new(&error[..]).size(16).color(ERROR_COLOR));
+        }
+        let mut export_button = Button::new(export_button, Text::new("Export"));
+        if can_export {
+            export_button = export_button.on_press(Message::Export);
+        }
+        let export_row = Row::new()
+            .spacing(20)
+            .push(Text::new("Export:"))
+            .push(PickList::new(
+                export_formats,
+                &ExportFormat::ALL[..],
+                Some(*export_format),
+                Message::SetExportFormat,
+            ))
+            .push(
+                TextInput::new(export_path_state, "File to export the results to", export_path, Message::ExportPathChanged)
+                    .on_submit(Message::Export)
+                    .padding(5),
+            )
+            .push(export_button)
+            .push(match export_status {
+                _ if *exporting => Text::new("Exporting . . .").size(16),
+                Some(Ok(done)) => Text::new(&done[..]).size(16),
+                Some(Err(error)) => Text::new(&error[..]).size(16).color(ERROR_COLOR),
+                None => Text::new(""),
+            });
+        let mode_row = Row::new()
+            .spacing(20)
+            .push(Text::new("Show:"))
+            .push(Radio::new(Mode::Files, "Matching files", Some(*mode), Message::SetMode))
+            .push(Radio::new(Mode::Lines, "Matching lines", Some(*mode), Message::SetMode));
+        let folding_row = Row::new()
+            .spacing(20)
+            .push(Text::new("Match:"))
+            .push(Radio::new(Folding::Case, "Ignoring case", Some(*folding), Message::SetFolding))
+            .push(Radio::new(Folding::Full, "Unicode case folding", Some(*folding), Message::SetFolding))
+            .push(Radio::new(Folding::Accents, "Also ignoring accents", Some(*folding), Message::SetFolding));
+        let results: Element<_> = match mode {
+            Mode::Files => Column::new()
+                .push(if *grouped {
+                    grouped_view(logs, collapsed, group_buttons, cursor.as_ref(), show_root)
+                } else {
+                    logs.iter_mut().fold(Column::new(), |col, log| {
+                        let selected = cursor.as_ref() == Some(&log.file);
+                        col.push(log_view(log, show_root, selected))
                     })
-                .push(Text::new(if unsearched_files.len() != 0 {"Caluclating Speed..."} else {speed_text}));
+                })
+                .push(if *trimmed {
+                    Row::with_children(vec![Text::new(format!(
+                        "Showing the first {} in this order. Use search to narrow down results.",
+                        LOG_MAX
+                    ))
+                    .width(Length::Fill)
+                    .horizontal_alignment(HorizontalAlignment::Center)
+                    .into()])
+                    .spacing(10)
+                } else {
+                    Row::with_children(vec![Text::new(if searching {
+                        "Searching . . ."
+                    } else {
+                        "Showing all results."
+                    })
+                    .width(Length::Fill)
+                    .horizontal_alignment(HorizontalAlignment::Center)
+                    .into()])
+                })
+                .into(),
+            Mode::Lines if search.is_none() => Text::new("Type a search to see the lines that match it.")
+                .width(Length::Fill)
+                .horizontal_alignment(HorizontalAlignment::Center)
+                .into(),
+            Mode::Lines => {
+                let pages = lines.len().div_ceil(LINES_PER_PAGE);
+                let page = (*page).min(pages.saturating_sub(1));
+                let first = page * LINES_PER_PAGE;
+                let shown = &lines[first..lines.len().min(first + LINES_PER_PAGE)];
+                let status = if lines.is_empty() && searching {
+                    "Searching . . .".to_string()
+                } else if lines.is_empty() {
+                    "No matching lines.".to_string()
+                } else {
+                    format!(
+                        "Lines {} to {} of {}{}",
+                        first + 1,
+                        first + shown.len(),
+                        lines.len(),
+                        if lines.len() == LINE_MAX {
+                            ", stopped at the limit. Use search to narrow down results."
+                        } else if searching {
+                            ", searching . . ."
+                        } else {
+                            ""
+                        }
+                    )
+                };
+                let mut prev = Button::new(prev_page_button, Text::new("Previous"));
+                if page > 0 {
+                    prev = prev.on_press(Message::Page(page - 1));
+                }
+                let mut next = Button::new(next_page_button, Text::new("Next"));
+                if page + 1 < pages {
+                    next = next.on_press(Message::Page(page + 1));
+                }
+                let pager = Row::new()
+                    .spacing(20)
+                    .push(prev)
+                    .push(Text::new(status).width(Length::Fill).horizontal_alignment(HorizontalAlignment::Center))
+                    .push(next);
+                Column::new()
+                    .spacing(10)
+                    .push(shown.iter().fold(Column::new().spacing(10), |col, line| {
+                        col.push(line_match_view(line, show_root))
+                    }))
+                    .push(pager)
+                    .into()
+            }
+        };
         let page: Element<_> = Column::new()
+            .push(saved_row)
+            .push(mode_row)
+            .push(if *mode == Mode::Files {
+                Row::new()
+                    .spacing(20)
+                    .push(Text::new("Sort:"))
+                    .push(PickList::new(sorts, &Sort::ALL[..], Some(*sort), Message::SetSort))
+                    .push(Checkbox::new(*grouped, "Group by directory", Message::SetGrouped))
+            } else {
+                Row::new()
+            })
+            .push(folding_row)
+            .push(export_row)
             .push(
                 search_bars
                     .iter_mut()
                     .enumerate()
                     .fold(Column::new(), |col, (i, bar)| {
+                        // past searches are suggested while typing in the first bar
+                        let suggestions = if i == 0 && bar.suggest {
+                            config.suggestions(&bar.value)
+                        } else {
+                            Vec::new()
+                        };
                         col.push(
-                            bar.view()
+                            bar.view(suggestions)
                                 .map(move |msg| Message::SearchChanged(i, msg)),
                         )
                     }),
             )
-            .push(logs.enumerate().fold(Column::new(), |col, (i, log)| {
-                col.push(log.view().map(move |msg| Message::Log(i, msg)))
-            }))
-            .push(if logs_count == LOG_MAX {
-                Row::with_children(vec![Text::new(format!(
-                    "Showing first {}. Use search to narrow down results.",
-                    LOG_MAX
-                ))
-                .width(Length::Fill)
-                .horizontal_alignment(HorizontalAlignment::Center)
-                .into()])
-                .spacing(10)
-            } else {
-                Row::with_children(vec![Text::new(if unsearched_files.len() > 0 {
-                    "Searching . . ."
-                } else {
-                    "Showing all results."
-                })
-                .width(Length::Fill)
-                .horizontal_alignment(HorizontalAlignment::Center)
-                .into()])
-            })
+            .push(filter_bar.view().map(Message::FilterChanged))
+            .push(file_bar.view().map(Message::FilesPicked))
+            .push(results)
+            .push(skipped_view(skipped, *skipped_open, skipped_button, show_root))
             .into();
-                let content = Column::new()
+                let mut content = Column::new()
                     .max_width(800)
                     .spacing(20)
-                    .push(speed_row)
+                    .push(speed_row);
+                if generator.open {
+                    content = content.push(generator.view(generating).map(|msg| match msg {
+                        GeneratorMessage::Generate => Message::MoarFiles,
+                        msg => Message::Generator(msg),
+                    }));
+                }
+                if generating {
+                    content = content.push(
+                        Row::new()
+                            .spacing(20)
+                            .push(ProgressBar::new(0.0..=*to_create as f32, *created as f32))
+                            .push(Text::new(format!("Created {} of {}", created, to_create)).size(16)),
+                    );
+                }
+                let content = content
+                    .push(stats_row)
                     .push(page);
 
                 Scrollable::new(scroll)
@@ -303,21 +1030,342 @@ impl Application for App {
 }
 
 impl State {
-    pub fn update_logs(&mut self) {
+    // List every file under the roots, which happens off the UI thread as it
+    // means going through the directories and reading each file's metadata.
+    pub fn update_logs(&mut self) -> Command<Message> {
         self.search_bars = vec![SearchBar::new(0)];
-        self.logs = if let Ok(files) = fs::read_dir(Path::new(LOGS)) {
-            files.map(|e| e.unwrap().file_name().to_string_lossy().to_string())
-            .take(LOG_MAX)
-            .map(|file| Log::new(file))
-            .collect()
-        } else {Vec::new()}
+        self.filter_bar.error = None;
+        self.cancel_search();
+        self.lines = Vec::new();
+        self.skipped = Vec::new();
+        self.page = 0;
+        self.logs = Vec::new();
+        if !self.select_files() {
+            return Command::none();
+        }
+        let generation = self.generation;
+        Command::perform(
+            list_logs(self.roots.clone(), self.selection.clone()),
+            move |files| Message::Listed(generation, files),
+        )
+    }
+
+    // Parse the search bars into one query and start searching every file under the roots.
+    // The files are listed and narrowed down by the index off the UI thread, then searched
+    // by the worker pool that `subscription` starts for `self.search` once they are back.
+    fn search(&mut self) -> Command<Message> {
+        let mut queries = Vec::with_capacity(self.search_bars.len());
+        let mut valid = true;
+        for bar in self.search_bars.iter_mut().filter(|bar| !bar.value.trim().is_empty()) {
+            let query = if bar.regex {
+                Query::regex(&bar.value).map_err(|e| e.to_string())
+            } else {
+                Query::parse(&bar.value).map_err(|e| e.to_string())
+            };
+            match query {
+                Ok(query) => {
+                    bar.error = None;
+                    queries.push(query);
+                }
+                Err(e) => {
+                    // show the error under the bar and wait for it to be fixed
+                    bar.error = Some(e);
+                    valid = false;
+                }
+            }
+        }
+        let filter = match self.filter_bar.filter() {
+            Ok(filter) => {
+                self.filter_bar.error = None;
+                filter
+            }
+            Err(e) => {
+                self.filter_bar.error = Some(e);
+                valid = false;
+                None
+            }
+        };
+        valid &= self.select_files();
+        self.logs = Vec::with_capacity(LOG_MAX);
+        self.lines = Vec::new();
+        self.skipped = Vec::new();
+        self.page = 0;
+        self.cancel_search();
+        if !valid {
+            return Command::none();
+        }
+        // every search bar is another AND
+        let query = Query::And(queries).fold(self.folding);
+        let target = self.file_bar.target;
+        // the index can tell which files are not worth opening at all,
+        // but only from their contents, a file's name might still match
+        let index = match &self.index {
+            Some(index) if target == Target::Contents || self.mode == Mode::Lines => Some(index.clone()),
+            _ => None,
+        };
+        let search = Search::new(self.generation, query, self.mode, Vec::new())
+            .with_filter(filter)
+            .with_target(target);
+        // there is a search from here on, `Message::Started` hands it its files
+        self.search = Some(search.clone());
+        self.stats = None;
+        Command::perform(
+            list_searched(search, self.roots.clone(), self.selection.clone(), index),
+            Message::Started,
+        )
+    }
+
+    // Pick the files to list or search from the file bar, showing what's wrong with its globs if anything.
+    fn select_files(&mut self) -> bool {
+        match self.file_bar.selection() {
+            Ok(selection) => {
+                self.file_bar.error = None;
+                self.selection = selection;
+                true
+            }
+            Err(e) => {
+                self.file_bar.error = Some(e);
+                false
+            }
+        }
+    }
+
+    // Add what searching a file found. A file can be searched more than once,
+    // by the pool and again when it changes, so it replaces what was there before.
+    fn add_found(&mut self, found: Found) {
+        match found {
+            Found::File(found) => {
+                self.skipped.retain(|skipped| skipped.file != found.file);
+                let old = self.logs.iter().position(|log| log.file == found.file).map(|at| self.logs.remove(at));
+                let mut log = Log::found(found);
+                // the new counts and snippets, but it stays open if it was
+                if let Some(old) = old {
+                    log.viewer = old.viewer;
+                    log.toggle_view_btn = old.toggle_view_btn;
+                }
+                self.insert_log(log);
+            }
+            Found::Lines(lines) => {
+                if let Some(file) = lines.first().map(|line| line.file.clone()) {
+                    self.lines.retain(|line| line.file != file);
+                    self.skipped.retain(|skipped| skipped.file != file);
+                }
+                self.lines.extend(lines);
+                self.lines.truncate(LINE_MAX);
+            }
+            // whatever it matched before can't be shown to still match
+            Found::Skipped(skipped) => {
+                self.logs.retain(|log| log.is_following() || log.file != skipped.file);
+                self.lines.retain(|line| line.file != skipped.file);
+                self.skipped.retain(|old| old.file != skipped.file);
+                self.skipped.push(skipped);
+            }
+        }
+    }
+
+    // Put the bars and options back the way they were saved and search again.
+    fn open_saved(&mut self, saved: SavedSearch) -> Command<Message> {
+        self.mode = saved.mode;
+        self.folding = saved.folding;
+        self.filter_bar.restore(saved.filter);
+        self.file_bar.restore(saved.files);
+        // the empty bar at the end is always there to add another term
+        self.search_bars = (0..=saved.bars.len()).map(SearchBar::new).collect();
+        for (bar, saved) in self.search_bars.iter_mut().zip(saved.bars) {
+            bar.value = saved.value;
+            bar.regex = saved.regex;
+        }
+        self.refresh()
+    }
+
+    // Search with what is in the bars and the filter, or list every file if there's nothing to search for.
+    fn refresh(&mut self) -> Command<Message> {
+        if self.search_bars.iter().all(|bar| bar.value.is_empty()) && !self.filter_bar.is_active() {
+            self.update_logs()
+        } else {
+            self.search()
+        }
+    }
+
+    // Add what is in the search bars to the history and save it,
+    // the first bar last so it ends up the most recent.
+    fn remember(&mut self) -> Command<Message> {
+        if self.search.is_none() {
+            return Command::none();
+        }
+        for bar in self.search_bars.iter().rev() {
+            if !bar.value.trim().is_empty() {
+                self.config.remember(&bar.value);
+            }
+        }
+        self.save_config()
+    }
+
+    fn save_config(&self) -> Command<Message> {
+        Command::perform(self.config.clone().save(), Message::ConfigSaved)
+    }
+
+    fn log_message(&mut self, file: LogFile, msg: LogMessage) -> Command<Message> {
+        // the results may have changed since, e.g. a file that was being opened
+        let log = match self.logs.iter_mut().find(|log| log.file == file) {
+            Some(log) => log,
+            None => return Command::none(),
+        };
+        let opening = log.viewer.is_none() && matches!(msg, LogMessage::ToggleView);
+        let command = log.update(msg).map(move |msg| Message::Log(file.clone(), msg));
+        // opening a result means the search found what was wanted
+        if opening {
+            Command::batch(vec![command, self.remember()])
+        } else {
+            command
+        }
+    }
+
+    // Only one input can have the keyboard, or what is typed goes into all of them.
+    fn unfocus_inputs(&mut self) {
+        for bar in &mut self.search_bars {
+            bar.state.unfocus();
+        }
+        self.filter_bar.unfocus();
+        self.file_bar.unfocus();
+        self.generator.unfocus();
+        self.save_name_state.unfocus();
+        self.export_path_state.unfocus();
+    }
+
+    fn focus_search_bar(&mut self, i: usize) {
+        self.unfocus_inputs();
+        let bar = &mut self.search_bars[i];
+        bar.state.focus();
+        bar.state.move_cursor_to_end();
+    }
+
+    // Move the keyboard cursor to the next or previous result on screen, which
+    // when grouping skips those in folded away directories. The page scrolls as far
+    // as the cursor moved so it stays where it was on screen. How far that is can only
+    // be worked out from what is in the results, iced knows their size once it draws them.
+    fn move_cursor(&mut self, down: bool) {
+        let shown = &self.logs;
+        // each result with the number of the directory it is under, folded away ones included
+        let order: Vec<(usize, usize)> = if self.grouped {
+            groups(shown)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, (dir, _))| !self.collapsed.contains(dir))
+                .flat_map(|(group, (_, members))| members.into_iter().map(move |i| (i, group)))
+                .collect()
+        } else {
+            (0..shown.len()).map(|i| (i, 0)).collect()
+        };
+        let at = self
+            .cursor
+            .as_ref()
+            .and_then(|file| order.iter().position(|&(i, _)| shown[i].file == *file));
+        let next = match at {
+            Some(at) if down => (at + 1).min(order.len().saturating_sub(1)),
+            Some(at) => at.saturating_sub(1),
+            // the first press lands on the first result whichever way it goes
+            None => 0,
+        };
+        if let Some(at) = at.filter(|&at| at != next) {
+            // the result above and the headers of the directories between the two
+            let ((above, group), (_, below_group)) = (order[at.min(next)], order[at.max(next)]);
+            let moved = shown[above].height() + (below_group - group) as f32 * HEADER_HEIGHT;
+            // the offset is only kept within the page once it is drawn, so any size that lets it move will do
+            let page = Rectangle { height: f32::MAX, ..Rectangle::default() };
+            self.scroll.scroll(if down { -moved } else { moved }, Rectangle::default(), page);
+        }
+        self.cursor = order.get(next).map(|&(i, _)| shown[i].file.clone());
+    }
+
+    // Add a result where the sort puts it, keeping only the first `LOG_MAX` of them.
+    // The ones that are open stay whatever comes before them.
+    fn insert_log(&mut self, log: Log) {
+        let at = self
+            .logs
+            .partition_point(|other| self.sort.compare(other, &log) != std::cmp::Ordering::Greater);
+        self.logs.insert(at, log);
+        while self.logs.len() > LOG_MAX {
+            match self.logs.iter().rposition(|log| log.viewer.is_none()) {
+                Some(last) => {
+                    self.logs.remove(last);
+                    self.trimmed = true;
+                }
+                None => break,
+            }
+        }
+    }
+
+    // a search needs a name and something to search for to be saved
+    fn can_save(&self) -> bool {
+        !self.save_name.trim().is_empty() && self.search_bars.iter().any(|bar| !bar.value.trim().is_empty())
+    }
+
+    // one export at a time, of something, to somewhere
+    fn can_export(&self) -> bool {
+        let has_results = match self.mode {
+            Mode::Files => !self.logs.is_empty(),
+            Mode::Lines => !self.lines.is_empty(),
+        };
+        has_results && !self.exporting && !self.export_path.trim().is_empty()
+    }
+
+    // Whether the search has found as many results as can be shown. Files are
+    // searched to the end whatever, a later one might come before those found so far.
+    fn is_full(&self) -> bool {
+        match self.mode {
+            Mode::Files => false,
+            Mode::Lines => self.lines.len() >= LINE_MAX,
+        }
+    }
+
+    // Search the files that changed again, on the pool once the search itself is done
+    // with it so the two aren't both going through the same files. One recheck runs
+    // at a time, the files that change meanwhile wait for the next.
+    fn recheck(&mut self) {
+        if self.unsearched > 0 || self.rechecks.is_some() || self.changed.is_empty() {
+            return;
+        }
+        if let Some(search) = &self.search {
+            let files = std::mem::take(&mut self.changed);
+            self.rechecking = files.len();
+            self.rechecks = Some(search.over(files));
+            self.pass += 1;
+        }
+    }
+
+    // Stop the current search, anything it still sends back is ignored
+    // because the generation has moved on.
+    fn cancel_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.cancel();
+        }
+        if let Some(rechecks) = self.rechecks.take() {
+            rechecks.cancel();
+        }
+        self.changed = Vec::new();
+        self.rechecking = 0;
+        self.generation += 1;
+        self.unsearched = 0;
+        self.trimmed = false;
+        // the numbers of a search that was cut short aren't worth showing
+        if !self.stats.as_ref().is_some_and(SearchStats::is_finished) {
+            self.stats = None;
+        }
     }
 }
 
 impl LoadState {
     // this is the function that is called to load data
-    async fn load() -> Result<LoadState, LoadError> {
-        Ok(LoadState{})
+    async fn load(flags: Flags) -> Result<LoadState, LoadError> {
+        let Flags { roots, threads, .. } = flags;
+        let (index, index_status) = Index::open(roots.clone()).await;
+        // a broken config just means starting without a history, the error says why
+        let (config, config_error) = match Config::load().await {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        };
+        Ok(LoadState{ roots, threads, index, index_status, config, config_error })
     }
 }
 
@@ -334,6 +1382,42 @@ fn loading_message<'a>() -> Element<'a, Message> {
     .into()
 }
 
+// What the keys do without the mouse, see `shortcut`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortcut {
+    // Ctrl+F
+    FocusSearch,
+    // Ctrl+Enter, to type another term
+    AddBar,
+    // Esc
+    Clear,
+    // the arrow keys move through the results and enter opens or closes one
+    Up,
+    Down,
+    Toggle,
+}
+
+// Turn a key press into a `Shortcut`. Keys pressed while typing in a text input
+// have been handled by it already, only the ones that still make sense are used.
+fn shortcut(event: iced_native::Event, status: event::Status) -> Option<Message> {
+    let (key_code, modifiers) = match event {
+        iced_native::Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers }) => (key_code, modifiers),
+        _ => return None,
+    };
+    let typing = status == event::Status::Captured;
+    let shortcut = match key_code {
+        KeyCode::F if modifiers.is_command_pressed() => Shortcut::FocusSearch,
+        KeyCode::Enter if modifiers.is_command_pressed() => Shortcut::AddBar,
+        KeyCode::Escape => Shortcut::Clear,
+        KeyCode::Up => Shortcut::Up,
+        KeyCode::Down => Shortcut::Down,
+        // enter in a text input submits it instead
+        KeyCode::Enter if !typing => Shortcut::Toggle,
+        _ => return None,
+    };
+    Some(Message::Shortcut(shortcut, typing))
+}
+
 // This is the logic for the search bars. Their state is stored in the main state.
 // It is best to keep your application's logic as 'flat' as possible, so if
 // you only have one search bar just keep the whole thing as part of the main logic.
@@ -344,12 +1428,20 @@ pub struct SearchBar {
     message: String,
     num: usize,
     value: String,
+    regex: bool,
+    error: Option<String>,
     state: text_input::State,
+    // whether to show past searches that start with `value`, until one is picked or enter is pressed
+    suggest: bool,
+    suggestion_buttons: Vec<button::State>,
 }
 
 #[derive(Clone, Debug)]
 pub enum SearchBarMessage {
     InputChanged(String),
+    ToggleRegex(bool),
+    Submitted,
+    Suggestion(String),
 }
 impl SearchBar {
     fn new(num: usize) -> Self {
@@ -360,18 +1452,22 @@ impl SearchBar {
                 "and:".to_string()
             },
             message: if num == 0 {
-                "Search".to_string()
+                "Search, e.g. timeout AND NOT retry".to_string()
             } else {
                 format!("Search term {} (Optional)", num + 1)
             },
             num,
             value: String::new(),
+            regex: false,
+            error: None,
             state: text_input::State::new(),
+            suggest: false,
+            suggestion_buttons: Vec::new(),
         }
     }
 
-    fn view(&mut self) -> Element<'_, SearchBarMessage> {
-        Row::new()
+    fn view(&mut self, suggestions: Vec<String>) -> Element<'_, SearchBarMessage> {
+        let bar = Row::new()
             .push((0..self.num).fold(Row::new(), |r, _i| {
                 r.push(Space::with_width(Length::Units(30)))
             }))
@@ -392,9 +1488,39 @@ impl SearchBar {
                     &self.value,
                     SearchBarMessage::InputChanged,
                 )
+                .on_submit(SearchBarMessage::Submitted)
                 .padding(10),
             )
-            .into()
+            .push(
+                Row::new()
+                    .push(Checkbox::new(self.regex, "Regex", SearchBarMessage::ToggleRegex))
+                    .padding(10),
+            );
+        let indent = Length::Units(30 * self.num as u16 + 100);
+        self.suggestion_buttons.resize_with(suggestions.len(), button::State::new);
+        let suggestions = self.suggestion_buttons.iter_mut().zip(suggestions).fold(
+            Column::new(),
+            |col, (state, suggestion)| {
+                col.push(
+                    Row::new().push(Space::with_width(indent)).push(
+                        Button::new(state, Text::new(&suggestion[..]).size(16))
+                            .padding(5)
+                            .on_press(SearchBarMessage::Suggestion(suggestion)),
+                    ),
+                )
+            },
+        );
+        // invalid patterns are shown right under the bar they belong to
+        let column = Column::new().push(bar);
+        let column = match &self.error {
+            Some(error) => column.push(
+                Row::new()
+                    .push(Space::with_width(indent))
+                    .push(Text::new(error).size(16).color(ERROR_COLOR)),
+            ),
+            None => column,
+        };
+        column.push(suggestions).into()
     }
 }
 
@@ -404,52 +1530,130 @@ impl SearchBar {
 // there are many optimizations for grabbing and storing the text
 // shown in the drop down, just for simplicity the text in each 'Log'
 // drop down is lazilly grabbed when opened.
+// While closed the first few matching lines are shown instead, these are
+// found by the search itself so they are ready as soon as the result is.
 #[derive(Clone, Debug)]
 pub struct Log {
+    file: LogFile,
     title: String,
-    content: String,
-    opened: bool,
+    // the query this file was found with, `None` when every file is listed
+    query: Option<Arc<Query>>,
+    snippets: Vec<Snippet>,
+    // how many times each term of the query is in the file
+    hits: Hits,
+    // how the file is compressed and how big it is decompressed, if it is
+    compressed: Option<(Compression, Option<u64>)>,
+    // `None` while only the snippets are shown
+    viewer: Option<Viewer>,
+    // for sorting, as it was when the file was found
+    modified: Option<SystemTime>,
+    size: u64,
     toggle_view_btn: button::State,
 }
 
 #[derive(Debug, Clone)]
 pub enum LogMessage {
     ToggleView,
+    Viewer(ViewerMessage),
 }
 impl Log {
-    pub fn new(title: String) -> Self {
+    // `info` is read beforehand by `list_logs` or `read_info`, off the UI thread
+    pub fn new(file: LogFile, info: FileInfo) -> Self {
         Log {
-            title,
-            content: "".to_string(), // leave empty until opened
-            opened: false,
+            modified: info.modified,
+            size: info.size,
+            compressed: info.compressed,
+            title: file.title(),
+            file,
+            query: None,
+            snippets: Vec::new(),
+            hits: Hits::default(),
+            viewer: None, // leave closed until opened
             toggle_view_btn: button::State::new(),
         }
     }
 
-    fn update(&mut self, message: LogMessage) {
+    pub fn found(found: FileMatch) -> Self {
+        Log {
+            query: Some(found.query),
+            snippets: found.snippets,
+            hits: found.hits,
+            ..Log::new(found.file, found.info)
+        }
+    }
+
+    // the height of the title and of the snippets or the opened file under it, roughly
+    fn height(&self) -> f32 {
+        HEADER_HEIGHT
+            + match &self.viewer {
+                Some(viewer) => viewer.height(),
+                None => 5.0 + lines_height(&self.snippets),
+            }
+    }
+
+    // an opened log that keeps up with its file, it is kept even if the file goes away
+    fn is_following(&self) -> bool {
+        self.viewer.as_ref().is_some_and(Viewer::is_following)
+    }
+
+    fn update(&mut self, message: LogMessage) -> Command<LogMessage> {
         match message {
-            LogMessage::ToggleView => {
-                if self.opened {
-                    self.opened = false;
-                } else {
-                    self.content =
-                        fs::read_to_string(Path::new(&format!("{}/{}", LOGS, &self.title)))
-                            .unwrap_or(format!("Error: Unable to read file {}!", &self.title));
-                    self.opened = true;
+            LogMessage::ToggleView => match self.viewer.take() {
+                Some(_) => Command::none(),
+                None => {
+                    let (viewer, command) = Viewer::open(self.file.path(), self.query.clone());
+                    self.viewer = Some(viewer);
+                    command.map(LogMessage::Viewer)
+                }
+            },
+            LogMessage::Viewer(message) => {
+                let viewer = match &mut self.viewer {
+                    Some(viewer) => viewer,
+                    None => return Command::none(),
+                };
+                let command = viewer.update(message);
+                // now that it has all been read the size is known even if the file didn't say
+                if let (Some((_, unknown @ None)), Some(index)) = (&mut self.compressed, viewer.index()) {
+                    *unknown = Some(index.bytes);
                 }
+                command.map(LogMessage::Viewer)
             }
         }
     }
 
-    fn view(&mut self) -> Element<'_, LogMessage> {
-        match self.opened {
-            true => Column::new()
+    // `selected` when the keyboard cursor is on it
+    fn view(&mut self, show_root: bool, selected: bool) -> Element<'_, LogMessage> {
+        let title = Row::new()
+            .spacing(10)
+            .push(if selected {
+                Text::new(&self.title).color(HIGHLIGHT_COLOR)
+            } else {
+                Text::new(&self.title)
+            })
+            .push(if show_root {
+                Text::new(self.file.root.to_string_lossy()).color(ROOT_COLOR)
+            } else {
+                Text::new("")
+            })
+            .push(Text::new(self.hits.text()).color(HITS_COLOR))
+            .push(match self.compressed {
+                Some((compression, Some(size))) => Text::new(format!(
+                    "{}, {:.1}MB uncompressed",
+                    compression.name(),
+                    size as f64 / 1_000_000.0
+                ))
+                .color(ROOT_COLOR),
+                Some((compression, None)) => Text::new(compression.name()).color(ROOT_COLOR),
+                None => Text::new(""),
+            });
+        match &mut self.viewer {
+            Some(viewer) => Column::new()
                 .push(
                     Button::new(
                         &mut self.toggle_view_btn,
                         Row::new()
                             .push(down_icon())
-                            .push(Text::new(&self.title)),
+                            .push(title),
                     )
                     .padding(10)
                     .width(Length::Fill)
@@ -457,28 +1661,185 @@ impl Log {
                 )
                 .push(
                     Row::new()
-                        .push(Text::new(&self.content).width(Length::Fill))
+                        .push(viewer.view().map(LogMessage::Viewer))
                         .padding(20),
                 )
                 .into(),
-            false => Column::new()
+            None => Column::new()
                 .spacing(5)
                 .push(
                     Button::new(
                         &mut self.toggle_view_btn,
                         Row::new()
                             .push(right_icon())
-                            .push(Text::new(&self.title)),
+                            .push(title),
                     )
                     .padding(10)
                     .width(Length::Fill)
                     .on_press(LogMessage::ToggleView),
                 )
+                .push(
+                    Row::new()
+                        .push(Space::with_width(Length::Units(30)))
+                        .push(lines_view(&self.snippets)),
+                )
                 .into(),
         }
     }
 }
 
+// A result, with what happens in it sent back for its file.
+fn log_view(log: &mut Log, show_root: bool, selected: bool) -> Element<'_, Message> {
+    let file = log.file.clone();
+    log.view(show_root, selected).map(move |msg| Message::Log(file.clone(), msg))
+}
+
+// The results under a header for each directory, each of which can be folded away.
+fn grouped_view<'a>(
+    logs: &'a mut [Log],
+    collapsed: &HashSet<PathBuf>,
+    buttons: &'a mut Vec<button::State>,
+    cursor: Option<&LogFile>,
+    show_root: bool,
+) -> Column<'a, Message> {
+    let groups = groups(logs);
+    buttons.resize_with(groups.len(), button::State::new);
+    let mut logs: Vec<Option<&'a mut Log>> = logs.iter_mut().map(Some).collect();
+    groups
+        .into_iter()
+        .zip(buttons.iter_mut())
+        .fold(Column::new(), |col, ((dir, members), button)| {
+            let open = !collapsed.contains(&dir);
+            let first = logs[members[0]].as_ref().expect("every result is in one group");
+            let relative = match first.file.relative.parent() {
+                Some(dir) if dir != Path::new("") => format!("{}/", dir.to_string_lossy()),
+                // straight in the root
+                _ => "./".to_string(),
+            };
+            let mut title = Row::new()
+                .spacing(10)
+                .push(Text::new(format!("{} ({})", relative, members.len())));
+            if show_root {
+                title = title.push(Text::new(first.file.root.to_string_lossy()).color(ROOT_COLOR));
+            }
+            let header = Button::new(
+                button,
+                Row::new()
+                    .push(if open { down_icon() } else { right_icon() })
+                    .push(title),
+            )
+            .padding(10)
+            .width(Length::Fill)
+            .on_press(Message::ToggleGroup(dir));
+            if !open {
+                return col.push(header);
+            }
+            let group = members.into_iter().fold(Column::new(), |group, i| {
+                let log = logs[i].take().expect("every result is in one group");
+                let selected = cursor == Some(&log.file);
+                group.push(log_view(log, show_root, selected))
+            });
+            col.push(header).push(
+                Row::new()
+                    .push(Space::with_width(Length::Units(20)))
+                    .push(group),
+            )
+        })
+}
+
+// The files a search couldn't look through and why, folded away under a count
+// like a closed `Log` as they are rarely what is being looked for.
+fn skipped_view<'a>(skipped: &[Skipped], open: bool, button: &'a mut button::State, show_root: bool) -> Element<'a, Message> {
+    if skipped.is_empty() {
+        return Space::with_height(Length::Units(0)).into();
+    }
+    let toggle = Button::new(
+        button,
+        Row::new()
+            .push(if open { down_icon() } else { right_icon() })
+            .push(Text::new(format!("Skipped files ({})", skipped.len()))),
+    )
+    .padding(10)
+    .width(Length::Fill)
+    .on_press(Message::ToggleSkipped);
+    if !open {
+        return toggle.into();
+    }
+    let list = skipped.iter().take(LOG_MAX).fold(Column::new().spacing(5), |col, skipped| {
+        let mut row = Row::new()
+            .spacing(10)
+            .push(Space::with_width(Length::Units(30)))
+            .push(Text::new(skipped.file.title()).size(16));
+        if show_root {
+            row = row.push(Text::new(skipped.file.root.to_string_lossy()).size(16).color(ROOT_COLOR));
+        }
+        // binary files are skipped on purpose, the rest are errors
+        let reason = Text::new(skipped.error.to_string()).size(16);
+        col.push(row.push(if skipped.error.is_deliberate() {
+            reason.color(ROOT_COLOR)
+        } else {
+            reason.color(ERROR_COLOR)
+        }))
+    });
+    let list = if skipped.len() > LOG_MAX {
+        list.push(Text::new(format!("and {} more", skipped.len() - LOG_MAX)).size(16))
+    } else {
+        list
+    };
+    Column::new().push(toggle).push(list).into()
+}
+
+// a single result in `Mode::Lines`, the file it is from above the line itself
+fn line_match_view<'a>(line: &LineMatch, show_root: bool) -> Element<'a, Message> {
+    let mut file = Row::new()
+        .spacing(10)
+        .push(Text::new(line.file.title()).size(16).color(ROOT_COLOR));
+    if show_root {
+        file = file.push(Text::new(line.file.root.to_string_lossy()).size(16).color(ROOT_COLOR));
+    }
+    Column::new()
+        .push(file)
+        .push(lines_view(std::slice::from_ref(&line.snippet)))
+        .into()
+}
+
+// Lines with a line number in front and the matches coloured in.
+// Every line becomes one or more rows of text pieces, see `Snippet::wrap`.
+// about how tall `lines_view` is, every line as it is wrapped
+fn lines_height(lines: &[Snippet]) -> f32 {
+    lines.iter().map(|line| line.wrap().len()).sum::<usize>() as f32 * LINE_HEIGHT
+}
+
+fn lines_view<'a, M: 'a>(lines: &[Snippet]) -> Column<'a, M> {
+    lines.iter().fold(Column::new().spacing(2), |col, line| {
+        line.wrap()
+            .into_iter()
+            .enumerate()
+            .fold(col, |col, (i, pieces)| {
+                let number = if i == 0 && line.line_number > 0 {
+                    line.line_number.to_string()
+                } else {
+                    String::new()
+                };
+                col.push(pieces.into_iter().fold(
+                    Row::new().push(
+                        Text::new(number)
+                            .size(16)
+                            .width(Length::Units(50))
+                            .color(LINE_NUMBER_COLOR),
+                    ),
+                    |row, (piece, highlighted)| {
+                        row.push(if highlighted {
+                            Text::new(piece).size(16).color(HIGHLIGHT_COLOR)
+                        } else {
+                            Text::new(piece).size(16)
+                        })
+                    },
+                ))
+            })
+    })
+}
+
 // some more simple helper functions
 
 // Fonts
@@ -488,7 +1849,7 @@ const ICONS_FONT: Font = Font::External {
 };
 
 fn icon(unicode: char) -> Text {
-    Text::new(&unicode.to_string())
+    Text::new(unicode.to_string())
         .font(ICONS_FONT)
         .width(Length::Units(20))
         .horizontal_alignment(HorizontalAlignment::Center)
@@ -501,23 +1862,35 @@ pub fn down_icon() -> Text {
     icon('\u{E802}')
 }
 
-async fn create_file() -> () {
-    let title = lipsum_title();
-    let _ = fs::write(format!("{}/{}.txt", LOGS, title), format!("{}.txt\n{}", title, lipsum(100)));
+// Every file under the roots with what is shown about it, all of them
+// as any one of them might be among the first in the sort.
+async fn list_logs(roots: Vec<PathBuf>, selection: Selection) -> Vec<(LogFile, FileInfo)> {
+    read_info(list_files(&roots, &selection)).await
 }
 
-// returns (time (ms), Mb/s)
-    async fn calc_speed(remaining: usize, start_time: SystemTime) -> Option<(u128, u128, u64)> {
-    let stop_time = SystemTime::now();
-    let mut files_len = 0;
-    let total_time = (stop_time.duration_since(UNIX_EPOCH).expect("Time went backwards") - start_time.duration_since(UNIX_EPOCH).expect("Time went backwards")).as_millis();
-    if let Ok(files) = fs::read_dir(Path::new(LOGS)) {
-        Some(( total_time,
-        ((files.into_iter().fold(0, |mut total_bytes, file| {
-            files_len += 1;
-            total_bytes += fs::metadata(file.unwrap().path()).unwrap().len();
-            total_bytes
-        })/files_len) as u128 * (files_len - remaining as u64) as u128/total_time) / 1048
-        , files_len))
-    } else {None}
-}
\ No newline at end of file
+// The files under the roots `search` goes through, leaving out the ones `index` rules out.
+async fn list_searched(mut search: Search, roots: Vec<PathBuf>, selection: Selection, index: Option<Arc<Index>>) -> Search {
+    let files = list_files(&roots, &selection);
+    let files = match index {
+        Some(index) => index.narrow(&search.query, files),
+        None => files,
+    };
+    search.files = Arc::new(files);
+    search
+}
+
+async fn read_info(files: Vec<LogFile>) -> Vec<(LogFile, FileInfo)> {
+    files
+        .into_iter()
+        .map(|file| {
+            let info = FileInfo::read(&file.path());
+            (file, info)
+        })
+        .collect()
+}
+
+async fn create_file(root: PathBuf, corpus: Arc<Corpus>, index: usize) -> Result<u64, String> {
+    corpus
+        .write(&root, index)
+        .map_err(|error| format!("Couldn't write {}: {}", root.join(corpus.path(index)).display(), error))
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+use aho_corasick::AhoCorasick;
+use regex::bytes::Regex;
+use serde::Serialize;
+
+use crate::fold::Folding;
+use crate::query::{Query, Term};
+
+// All of a query's terms compiled together, so a line is scanned once for every
+// term rather than once per term. Plain ASCII terms go into a single Aho-Corasick
+// automaton; regexes and terms with other characters, which the automaton can't
+// match case insensitively, are still looked for one at a time.
+// Folded terms get an automaton of their own that runs over the folded line.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    // the term each pattern of the automaton is for, as an index into `Query::terms`
+    automaton: Option<(AhoCorasick, Vec<usize>)>,
+    folded: Option<(Folding, AhoCorasick, Vec<usize>)>,
+    others: Vec<(usize, Regex)>,
+    // what the hit counts are shown as, `None` for terms under a NOT
+    labels: Vec<Option<String>>,
+}
+
+// How many times each term of the query was found in a file.
+#[derive(Debug, Clone, Default)]
+pub struct Hits(Vec<Hit>);
+
+// One of those terms, as it is also written out by `--format json` and the exports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hit {
+    pub term: String,
+    pub count: usize,
+}
+
+impl Matcher {
+    pub fn new(query: &Query) -> Self {
+        let mut patterns = Vec::new();
+        let mut pattern_terms = Vec::new();
+        let mut folded = Vec::new();
+        let mut folded_terms = Vec::new();
+        let mut folding = None;
+        let mut others = Vec::new();
+        for (i, term) in query.terms().into_iter().enumerate() {
+            match term {
+                Term::Plain(text, _) if text.is_ascii() => {
+                    patterns.push(text.clone());
+                    pattern_terms.push(i);
+                }
+                // every folded term in a search is folded the same way
+                Term::Folded(text, f) => {
+                    folded.push(text.clone());
+                    folded_terms.push(i);
+                    folding = Some(*f);
+                }
+                Term::Plain(_, re) | Term::Regex(re) => others.push((i, re.clone())),
+            }
+        }
+        let automaton = build(&patterns, true).map(|automaton| (automaton, pattern_terms));
+        let folded = match (folding, build(&folded, false)) {
+            (Some(folding), Some(automaton)) => Some((folding, automaton, folded_terms)),
+            _ => None,
+        };
+        Matcher {
+            automaton,
+            folded,
+            others,
+            labels: labels(query, false),
+        }
+    }
+
+    // the number of terms in the query, `count` needs this many counters
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    // a search on the filter alone has no terms at all
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    // Add how many times each term is in `line` to `counts`, returning whether any were.
+    pub fn count(&self, line: &[u8], counts: &mut [usize]) -> bool {
+        let mut found = false;
+        if let Some((automaton, terms)) = &self.automaton {
+            // overlapping, so "time" and "timeout" are both counted in "timeout"
+            for m in automaton.find_overlapping_iter(line) {
+                counts[terms[m.pattern().as_usize()]] += 1;
+                found = true;
+            }
+        }
+        if let Some((folding, automaton, terms)) = &self.folded {
+            let line = folding.fold(&String::from_utf8_lossy(line));
+            for m in automaton.find_overlapping_iter(&line) {
+                counts[terms[m.pattern().as_usize()]] += 1;
+                found = true;
+            }
+        }
+        for (i, re) in &self.others {
+            let n = re.find_iter(line).count();
+            counts[*i] += n;
+            found |= n > 0;
+        }
+        found
+    }
+
+    // whether `line` on its own matches `query`, which this was made from
+    pub fn matches(&self, query: &Query, line: &[u8]) -> bool {
+        let mut counts = vec![0; self.len()];
+        self.count(line, &mut counts);
+        let terms = query.terms();
+        query
+            .decide(&|term| Some(counts[index_of(&terms, term)] > 0))
+            .expect("every term is known")
+    }
+
+    pub fn hits(&self, counts: &[usize]) -> Hits {
+        Hits(
+            self.labels
+                .iter()
+                .zip(counts)
+                .filter_map(|(label, &count)| Some(Hit { term: label.clone()?, count }))
+                .collect(),
+        )
+    }
+}
+
+impl Hits {
+    // each term that isn't under a NOT, with how many times it was found
+    pub fn counts(&self) -> &[Hit] {
+        &self.0
+    }
+
+    // how many times all the terms were found together
+    pub fn total(&self) -> usize {
+        self.0.iter().map(|hit| hit.count).sum()
+    }
+
+    // e.g. "timeout ×12  retry ×3"
+    pub fn text(&self) -> String {
+        self.0.iter().map(Hit::to_string).collect::<Vec<_>>().join("  ")
+    }
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ×{}", self.term, self.count)
+    }
+}
+
+fn build(patterns: &[String], ascii_case_insensitive: bool) -> Option<AhoCorasick> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let automaton = AhoCorasick::builder()
+        .ascii_case_insensitive(ascii_case_insensitive)
+        .build(patterns)
+        .expect("plain text always builds");
+    Some(automaton)
+}
+
+// The position of `term` in `terms`, which has to come from the same `Query`.
+// Terms are told apart by address so the same word twice is still two terms.
+pub fn index_of(terms: &[&Term], term: &Term) -> usize {
+    terms
+        .iter()
+        .position(|t| std::ptr::eq(*t, term))
+        .expect("the term is from this query")
+}
+
+fn labels(query: &Query, negated: bool) -> Vec<Option<String>> {
+    match query {
+        Query::Term(_) if negated => vec![None],
+        Query::Term(Term::Plain(text, _)) | Query::Term(Term::Folded(text, _)) => vec![Some(text.clone())],
+        Query::Term(Term::Regex(re)) => vec![Some(format!("/{}/", re.as_str()))],
+        Query::Not(query) => labels(query, !negated),
+        Query::And(queries) | Query::Or(queries) => {
+            queries.iter().flat_map(|query| labels(query, negated)).collect()
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::hash::{Hash, Hasher};
+use iced::futures::{self, channel::mpsc, stream::BoxStream, StreamExt};
+use iced_native::subscription::Recipe;
+
+use multi_threaded_search::files::LogFile;
+use multi_threaded_search::logger::{spawn_workers, Found, Search};
+use multi_threaded_search::stats::FileStats;
+
+// What the workers found since the last batch was handed to the UI.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub generation: u64,
+    pub found: Vec<Found>,
+    // one for every file searched, including the ones that didn't match
+    pub stats: Vec<FileStats>,
+    // the files searched that found nothing, which a recheck takes out of the results
+    pub missed: Vec<LogFile>,
+}
+
+// Search every file in `search.files` on a pool of `threads` worker threads,
+// streaming back what they find for as long as the subscription is active.
+// Dropping the subscription (or cancelling the search) stops the workers
+// once they finish the file they are on. `pass` tells the runs of one search
+// apart, 0 for the search itself and counting up for the files rechecked after it.
+pub fn run(search: Search, pass: u64, threads: usize) -> iced::Subscription<Batch> {
+    iced::Subscription::from_recipe(SearchPool { search, pass, threads })
+}
+
+struct SearchPool {
+    search: Search,
+    pass: u64,
+    threads: usize,
+}
+
+impl<H, I> Recipe<H, I> for SearchPool
+where
+    H: Hasher,
+{
+    type Output = Batch;
+
+    fn hash(&self, state: &mut H) {
+        std::any::TypeId::of::<Self>().hash(state);
+        // a new search is a new generation, which replaces the pool of the one before
+        self.search.generation.hash(state);
+        self.pass.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _input: BoxStream<'static, I>) -> BoxStream<'static, Self::Output> {
+        let (sender, receiver) = mpsc::unbounded();
+        spawn_workers(&self.search, self.threads, move |file, found, stats| {
+            // the receiver is gone once the UI has no use for more results
+            let missed = found.is_none().then(|| file.clone());
+            sender.unbounded_send((found, stats, missed)).is_ok()
+        });
+        // the stream ends once every worker has finished and dropped its sender
+        let generation = self.search.generation;
+        Box::pin(futures::stream::unfold(receiver, move |mut receiver| async move {
+            let mut batch = Batch {
+                generation,
+                found: Vec::new(),
+                stats: Vec::new(),
+                missed: Vec::new(),
+            };
+            batch.add(receiver.next().await?);
+            // everything that came in while the UI was busy goes in the same batch
+            while let Ok(found) = receiver.try_recv() {
+                batch.add(found);
+            }
+            Some((batch, receiver))
+        }))
+    }
+}
+
+impl Batch {
+    fn add(&mut self, (found, stats, missed): (Option<Found>, FileStats, Option<LogFile>)) {
+        self.found.extend(found);
+        self.stats.push(stats);
+        self.missed.extend(missed);
+    }
+}
@@ -0,0 +1,459 @@
+use std::fmt;
+use std::ops::Range;
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::fold::Folding;
+
+// The query language typed into a `SearchBar`:
+//
+//   timeout AND NOT retry
+//   "connection reset" OR refused
+//   (error OR warn) -debug
+//
+// Terms next to each other are ANDed, `-term` is short for `NOT term` and
+// quotes allow matching phrases containing spaces or the operator words.
+// Operators are only recognised in upper case so `and` is still searchable.
+// Matching is always case insensitive, see `Folding` for how much.
+// Every search bar is parsed into a `Query` and the bars are ANDed together.
+#[derive(Debug, Clone)]
+pub enum Query {
+    Term(Term),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+// A leaf of the query, the thing that is actually looked for in a file.
+// Both kinds are compiled to a case insensitive regex over bytes once when the
+// search starts, so files are matched as they are read without lowercasing a copy
+// of them first, and files that aren't valid UTF-8 can still be searched.
+// Cloning a `Regex` is cheap as the compiled program is shared.
+#[derive(Debug, Clone)]
+pub enum Term {
+    // the lowercased text, used by the index, and the text as a pattern
+    Plain(String, Regex),
+    // plain text that is matched against the folded line, kept already folded
+    Folded(String, Folding),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    message: String,
+    // character offset into the input, shown 1-based to the user
+    position: usize,
+}
+
+impl Query {
+    // parse a search bar's text using the query language above
+    pub fn parse(input: &str) -> Result<Query, ParseError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: input.chars().count(),
+        };
+        let query = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            // the only way `or()` stops early is an unbalanced `)`
+            Some(token) => Err(ParseError::new("Unmatched ')'", token.position)),
+            None => Ok(query),
+        }
+    }
+
+    // a search bar in regex mode is a single pattern, not a query
+    pub fn regex(pattern: &str) -> Result<Query, regex::Error> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map(|re| Query::Term(Term::Regex(re)))
+    }
+
+    // Switch the plain terms over to `folding`, regexes are left alone.
+    pub fn fold(self, folding: Folding) -> Query {
+        match self {
+            Query::Term(Term::Plain(text, re)) => {
+                let folded = folding.fold(&text);
+                // a term of nothing but accents would otherwise match everywhere
+                if folding == Folding::Case || folded.is_empty() {
+                    Query::Term(Term::Plain(text, re))
+                } else {
+                    Query::Term(Term::Folded(folded, folding))
+                }
+            }
+            Query::Term(term) => Query::Term(term),
+            Query::Not(query) => Query::Not(Box::new(query.fold(folding))),
+            Query::And(queries) => Query::And(queries.into_iter().map(|q| q.fold(folding)).collect()),
+            Query::Or(queries) => Query::Or(queries.into_iter().map(|q| q.fold(folding)).collect()),
+        }
+    }
+
+    // Whether the query matches given whether each term was found, `None` for terms
+    // that aren't known yet. Returns `None` if the answer depends on those terms,
+    // this is what lets a file scan stop before the end of the file.
+    pub fn decide(&self, found: &dyn Fn(&Term) -> Option<bool>) -> Option<bool> {
+        match self {
+            Query::Term(term) => found(term),
+            Query::Not(query) => query.decide(found).map(|found| !found),
+            // one side known to be false decides an AND, one known to be true decides an OR
+            Query::And(queries) => decide_all(queries, found, false),
+            Query::Or(queries) => decide_all(queries, found, true),
+        }
+    }
+
+    // every term in the query, in the order they were typed
+    pub fn terms(&self) -> Vec<&Term> {
+        match self {
+            Query::Term(term) => vec![term],
+            Query::Not(query) => query.terms(),
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().flat_map(|query| query.terms()).collect()
+            }
+        }
+    }
+
+    // Where the terms that make `line` match are, as sorted byte ranges into `line`.
+    // Terms under a NOT are never highlighted, they are what must *not* be there.
+    pub fn highlights(&self, line: &str) -> Vec<Range<usize>> {
+        let mut found = Vec::new();
+        self.find(line, &mut None, &mut found);
+        found.sort_by_key(|range| range.start);
+        // overlapping terms are merged into one highlight
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(found.len());
+        for range in found {
+            match ranges.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => ranges.push(range),
+            }
+        }
+        ranges
+    }
+
+    // `folded` is the line folded for `Term::Folded`, only worked out if there is one
+    fn find(&self, line: &str, folded: &mut Option<(String, Vec<usize>)>, found: &mut Vec<Range<usize>>) {
+        match self {
+            Query::Term(Term::Plain(_, re)) | Query::Term(Term::Regex(re)) => found.extend(
+                re.find_iter(line.as_bytes())
+                    .filter(|m| !m.as_bytes().is_empty())
+                    .map(|m| m.range()),
+            ),
+            Query::Term(Term::Folded(text, folding)) => {
+                let (folded, offsets) = folded.get_or_insert_with(|| folding.fold_with_offsets(line));
+                found.extend(folded.match_indices(&text[..]).map(|(i, m)| {
+                    // a match can end part way through what a character folded to,
+                    // the whole of that character is highlighted
+                    let last = offsets[i + m.len() - 1];
+                    let end = last + line[last..].chars().next().map_or(0, char::len_utf8);
+                    offsets[i]..end
+                }));
+            }
+            Query::Not(_) => (),
+            Query::And(queries) | Query::Or(queries) => {
+                for query in queries {
+                    query.find(line, folded, found);
+                }
+            }
+        }
+    }
+}
+
+fn decide_all(queries: &[Query], found: &dyn Fn(&Term) -> Option<bool>, decider: bool) -> Option<bool> {
+    let mut decided = Some(!decider);
+    for query in queries {
+        match query.decide(found) {
+            Some(result) if result == decider => return Some(decider),
+            Some(_) => (),
+            None => decided = None,
+        }
+    }
+    decided
+}
+
+impl Term {
+    // An escaped pattern is always valid, but a long enough one pasted in can still
+    // be too big to compile. `position` is where the term starts, for the error.
+    fn plain(text: &str, position: usize) -> Result<Term, ParseError> {
+        let re = RegexBuilder::new(&regex::escape(text))
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| match e {
+                regex::Error::CompiledTooBig(_) => ParseError::new("Search term is too long", position),
+                e => ParseError::new(e.to_string(), position),
+            })?;
+        Ok(Term::Plain(text.to_lowercase(), re))
+    }
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        ParseError {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Word(String),
+    Phrase(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some((position, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            // only a leading `-` negates, `foo-bar` is a single word
+            '-' if chars.peek().is_some_and(|(_, c)| !c.is_whitespace()) => TokenKind::Not,
+            '"' => {
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => phrase.push(c),
+                        None => return Err(ParseError::new("Unterminated quote", position)),
+                    }
+                }
+                // an empty phrase would match everywhere
+                if phrase.trim().is_empty() {
+                    return Err(ParseError::new("Nothing to search for in quotes", position));
+                }
+                TokenKind::Phrase(phrase)
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.peek() {
+                    if c.is_whitespace() || *c == '(' || *c == ')' || *c == '"' {
+                        break;
+                    }
+                    word.push(*c);
+                    chars.next();
+                }
+                match &word[..] {
+                    "AND" => TokenKind::And,
+                    "OR" => TokenKind::Or,
+                    "NOT" => TokenKind::Not,
+                    _ => TokenKind::Word(word),
+                }
+            }
+        };
+        tokens.push(Token { kind, position });
+    }
+    Ok(tokens)
+}
+
+// A recursive descent parser, lowest precedence first:
+//   or    = and { "OR" and }
+//   and   = unary { ["AND"] unary }
+//   unary = "NOT" unary | primary
+//   primary = "(" or ")" | word | phrase
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // used to point at the end of the input for "expected ..." errors
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|token| &token.kind)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.and()?];
+        while self.peek() == Some(&TokenKind::Or) {
+            self.next();
+            queries.push(self.and()?);
+        }
+        Ok(flatten(queries, Query::Or))
+    }
+
+    fn and(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(TokenKind::And) => {
+                    self.next();
+                    queries.push(self.unary()?);
+                }
+                Some(TokenKind::Or) | Some(TokenKind::RParen) | None => break,
+                // two terms next to each other are an implicit AND
+                Some(_) => queries.push(self.unary()?),
+            }
+        }
+        Ok(flatten(queries, Query::And))
+    }
+
+    fn unary(&mut self) -> Result<Query, ParseError> {
+        if self.peek() == Some(&TokenKind::Not) {
+            self.next();
+            Ok(Query::Not(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Query, ParseError> {
+        match self.next() {
+            Some(Token { kind: TokenKind::LParen, position }) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token { kind: TokenKind::RParen, .. }) => Ok(query),
+                    _ => Err(ParseError::new("Unclosed '('", position)),
+                }
+            }
+            Some(Token { kind: TokenKind::Word(val), position })
+            | Some(Token { kind: TokenKind::Phrase(val), position }) => {
+                Ok(Query::Term(Term::plain(&val, position)?))
+            }
+            Some(Token { kind, position }) => Err(ParseError::new(
+                format!("Expected a search term but found {}", describe(&kind)),
+                position,
+            )),
+            None => Err(ParseError::new("Expected a search term", self.end)),
+        }
+    }
+}
+
+fn flatten(mut queries: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    if queries.len() == 1 {
+        queries.remove(0)
+    } else {
+        combine(queries)
+    }
+}
+
+fn describe(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::LParen => "'('",
+        TokenKind::RParen => "')'",
+        TokenKind::And => "AND",
+        TokenKind::Or => "OR",
+        TokenKind::Not => "NOT",
+        TokenKind::Word(_) | TokenKind::Phrase(_) => "a search term",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the tree a query parses to, written out as e.g. `OR(a, AND(b, c))`
+    fn shape(input: &str) -> String {
+        fn write(query: &Query) -> String {
+            let list = |queries: &[Query]| queries.iter().map(write).collect::<Vec<_>>().join(", ");
+            match query {
+                Query::Term(Term::Plain(text, _)) => text.clone(),
+                Query::Term(Term::Folded(text, _)) => text.clone(),
+                Query::Term(Term::Regex(re)) => format!("/{}/", re.as_str()),
+                Query::Not(query) => format!("NOT({})", write(query)),
+                Query::And(queries) => format!("AND({})", list(queries)),
+                Query::Or(queries) => format!("OR({})", list(queries)),
+            }
+        }
+        write(&Query::parse(input).unwrap())
+    }
+
+    fn error(input: &str) -> String {
+        Query::parse(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(shape("a OR b c"), "OR(a, AND(b, c))");
+        assert_eq!(shape("a b OR c"), "OR(AND(a, b), c)");
+        assert_eq!(shape("a AND b OR c AND d"), "OR(AND(a, b), AND(c, d))");
+        assert_eq!(shape("a AND b c"), "AND(a, b, c)");
+    }
+
+    #[test]
+    fn not_binds_tightest() {
+        assert_eq!(shape("NOT a b"), "AND(NOT(a), b)");
+        assert_eq!(shape("a -b OR c"), "OR(AND(a, NOT(b)), c)");
+        assert_eq!(shape("NOT NOT a"), "NOT(NOT(a))");
+        assert_eq!(shape("-(a OR b)"), "NOT(OR(a, b))");
+    }
+
+    #[test]
+    fn parentheses_group() {
+        assert_eq!(shape("(a OR b) c"), "AND(OR(a, b), c)");
+        assert_eq!(shape("(error OR warn) -debug"), "AND(OR(error, warn), NOT(debug))");
+        assert_eq!(shape("((a))"), "a");
+    }
+
+    #[test]
+    fn quotes_make_one_term() {
+        assert_eq!(shape("\"connection reset\" OR refused"), "OR(connection reset, refused)");
+        // quoted operators are searched for, lower case ones are just words
+        assert_eq!(shape("\"AND\" \"NOT\""), "AND(and, not)");
+        assert_eq!(shape("a and b"), "AND(a, and, b)");
+        // a quote ends a word, and only a leading `-` negates
+        assert_eq!(shape("foo\"bar baz\""), "AND(foo, bar baz)");
+        assert_eq!(shape("foo-bar"), "foo-bar");
+    }
+
+    #[test]
+    fn terms_are_lowercased_for_the_index() {
+        assert_eq!(shape("TimeOut"), "timeout");
+        assert_eq!(Query::parse("TimeOut").unwrap().highlights("a TIMEOUT b"), vec![2..9]);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(error("\"abc"), "Unterminated quote at column 1");
+        assert_eq!(error("a \"\""), "Nothing to search for in quotes at column 3");
+        assert_eq!(error("a )"), "Unmatched ')' at column 3");
+        assert_eq!(error("(a"), "Unclosed '(' at column 1");
+        assert_eq!(error("()"), "Expected a search term but found ')' at column 2");
+        assert_eq!(error("a AND"), "Expected a search term at column 6");
+        assert_eq!(error("a OR OR b"), "Expected a search term but found OR at column 6");
+        assert_eq!(error(""), "Expected a search term at column 1");
+    }
+
+    #[test]
+    fn term_too_big_to_compile_is_an_error() {
+        let input = format!("a {}", "x".repeat(1_000_000));
+        assert_eq!(error(&input), "Search term is too long at column 3");
+    }
+
+    #[test]
+    fn decides_as_soon_as_it_can() {
+        let query = Query::parse("a -b").unwrap();
+        let terms = query.terms();
+        // whether `a` and `b` were found, `None` for not known yet
+        let decide = |a: Option<bool>, b: Option<bool>| {
+            query.decide(&|term: &Term| if std::ptr::eq(term, terms[0]) { a } else { b })
+        };
+        assert_eq!(decide(Some(false), None), Some(false));
+        assert_eq!(decide(Some(true), None), None);
+        assert_eq!(decide(Some(true), Some(false)), Some(true));
+        assert_eq!(decide(Some(true), Some(true)), Some(false));
+    }
+}
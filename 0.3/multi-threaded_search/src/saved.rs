@@ -0,0 +1,127 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+
+use multi_threaded_search::fold::Folding;
+use multi_threaded_search::logger::Mode;
+
+use crate::file_bar::SavedFiles;
+use crate::filter_bar::SavedFilter;
+
+// how many past searches are kept, the oldest are forgotten first
+const HISTORY_MAX: usize = 50;
+// how many of them are suggested under the first search bar
+const SUGGESTIONS_MAX: usize = 5;
+
+// numbers the files saves are written to first, see `Config::save`
+static SAVES: AtomicUsize = AtomicUsize::new(0);
+
+// The search history and the saved searches, kept in the user's config directory
+// so they are still there the next time the app is opened.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // newest first
+    pub history: Vec<String>,
+    // sorted by name
+    pub saved: Vec<SavedSearch>,
+}
+
+// Everything needed to run a search again: what was in each bar and how it was searched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub bars: Vec<SavedBar>,
+    pub mode: Mode,
+    pub folding: Folding,
+    // saved before there were filters, these don't have one
+    #[serde(default)]
+    pub filter: SavedFilter,
+    // and before files could be picked, these search every file's contents
+    #[serde(default)]
+    pub files: SavedFiles,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedBar {
+    pub value: String,
+    pub regex: bool,
+}
+
+impl Config {
+    // e.g. ~/.config/multi-threaded_search/searches.json on Linux
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("multi-threaded_search").join("searches.json"))
+    }
+
+    // Nothing saved yet is the same as an empty config.
+    pub async fn load() -> Result<Config, String> {
+        let path = match Config::path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Unable to read saved searches from {}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("Unable to read saved searches from {}: {}", path.display(), e)),
+        }
+    }
+
+    pub async fn save(self) -> Result<(), String> {
+        let path = Config::path().ok_or_else(|| "No config directory to save searches in".to_string())?;
+        let json = serde_json::to_vec_pretty(&self).map_err(|e| e.to_string())?;
+        // written next to it and moved over, so a save that fails half way
+        // or two saves at once can't leave a broken file behind. Each save has
+        // a file of its own, whichever app and whichever save in it it is.
+        let save = SAVES.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_extension(format!("json.{}.{}", std::process::id(), save));
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&tmp, json))
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| format!("Unable to save searches to {}: {}", path.display(), e))
+    }
+
+    // Move `search` to the front of the history, it is only ever in there once.
+    pub fn remember(&mut self, search: &str) {
+        self.history.retain(|old| old != search);
+        self.history.insert(0, search.to_string());
+        self.history.truncate(HISTORY_MAX);
+    }
+
+    // The most recent past searches starting with what has been typed so far.
+    pub fn suggestions(&self, typed: &str) -> Vec<String> {
+        let typed = typed.to_lowercase();
+        self.history
+            .iter()
+            .filter(|old| {
+                let old = old.to_lowercase();
+                old.starts_with(&typed) && old != typed
+            })
+            .take(SUGGESTIONS_MAX)
+            .cloned()
+            .collect()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.saved.iter().map(|search| search.name.clone()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SavedSearch> {
+        self.saved.iter().find(|search| search.name == name)
+    }
+
+    // Saving under a name that is already used replaces that search.
+    pub fn save_search(&mut self, search: SavedSearch) {
+        self.delete(&search.name);
+        self.saved.push(search);
+        self.saved.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    pub fn delete(&mut self, name: &str) {
+        self.saved.retain(|search| search.name != name);
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use flate2::read::MultiGzDecoder;
+
+// the size of the chunks files are read in
+const CHUNK_SIZE: usize = 64 * 1024;
+// Lines longer than this are cut short and the rest of them read past, so a file
+// without line endings, e.g. a minified dump, isn't read into memory all at once.
+// Anything in the part that was cut off isn't searched.
+pub const LINE_BYTES_MAX: usize = 1024 * 1024;
+
+// how far into a file to look for a NUL byte, the same as git does
+const BINARY_CHECK: usize = 8000;
+// the first bytes of every gzip member and zstd frame
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+// the most a zstd frame header can take up
+const ZSTD_HEADER_MAX: usize = 18;
+
+// How a file is compressed. This is told by its first few bytes rather than its name,
+// so rotated logs like `app.log.1` that were compressed are still found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn detect(start: &[u8]) -> Option<Compression> {
+        if start.starts_with(&GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if start.starts_with(&ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    // `None` for a file that isn't compressed, or can't be read
+    pub fn of(path: &Path) -> Option<Compression> {
+        let mut start = Vec::with_capacity(ZSTD_MAGIC.len());
+        File::open(path).ok()?.take(ZSTD_MAGIC.len() as u64).read_to_end(&mut start).ok()?;
+        Compression::detect(&start)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        }
+    }
+
+    // How big the file is decompressed, without decompressing it. gzip keeps the size
+    // of the last member at the end, which is the whole file unless members were joined
+    // together, and zstd has it at the start of the frame when it was known when
+    // compressing. `None` if the file doesn't say.
+    pub fn uncompressed_size(self, path: &Path) -> Option<u64> {
+        let mut file = File::open(path).ok()?;
+        match self {
+            Compression::Gzip => {
+                let mut size = [0; 4];
+                file.seek(SeekFrom::End(-4)).ok()?;
+                file.read_exact(&mut size).ok()?;
+                // only the size modulo 4GB fits in the trailer, so anything
+                // bigger than that decompressed is shown too small
+                Some(u32::from_le_bytes(size) as u64)
+            }
+            Compression::Zstd => {
+                let mut header = Vec::with_capacity(ZSTD_HEADER_MAX);
+                file.take(ZSTD_HEADER_MAX as u64).read_to_end(&mut header).ok()?;
+                zstd::zstd_safe::get_frame_content_size(&header).ok()?
+            }
+        }
+    }
+}
+
+// Why a file couldn't be searched. Any of these only skips that one file,
+// the rest of the search carries on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileError {
+    // deleted since the directory was listed
+    NotFound,
+    PermissionDenied,
+    // not text, skipped on purpose rather than searching through it
+    Binary,
+    // a compressed file that is cut short or otherwise broken
+    Corrupt(String),
+    Other(String),
+}
+
+impl FileError {
+    // whether the file was skipped on purpose rather than because something went wrong
+    pub fn is_deliberate(&self) -> bool {
+        *self == FileError::Binary
+    }
+}
+
+impl From<io::Error> for FileError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => FileError::NotFound,
+            io::ErrorKind::PermissionDenied => FileError::PermissionDenied,
+            io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput | io::ErrorKind::UnexpectedEof => {
+                FileError::Corrupt(e.to_string())
+            }
+            _ => FileError::Other(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::NotFound => f.write_str("No longer there"),
+            FileError::PermissionDenied => f.write_str("Permission denied"),
+            FileError::Binary => f.write_str("Binary file"),
+            FileError::Corrupt(e) => write!(f, "Unable to decompress: {}", e),
+            FileError::Other(e) => f.write_str(e),
+        }
+    }
+}
+
+// Reads a file a line at a time into the same buffer, so however big the file is
+// only one chunk and the current line are ever in memory.
+// Lines are raw bytes, a file that isn't valid UTF-8 is still searched.
+// Compressed files are decompressed as they are read, the lines are those of the
+// decompressed file.
+pub struct LineReader<R> {
+    reader: R,
+    line: Vec<u8>,
+    line_number: usize,
+    bytes_read: u64,
+    // whether the last line read had a line ending
+    ended: bool,
+}
+
+impl LineReader<Box<dyn BufRead + Send>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = BufReader::with_capacity(CHUNK_SIZE, File::open(path)?);
+        let reader: Box<dyn BufRead + Send> = match Compression::detect(file.fill_buf()?) {
+            None => Box::new(file),
+            Some(Compression::Gzip) => Box::new(BufReader::with_capacity(CHUNK_SIZE, MultiGzDecoder::new(file))),
+            Some(Compression::Zstd) => Box::new(BufReader::with_capacity(CHUNK_SIZE, zstd::Decoder::with_buffer(file)?)),
+        };
+        Ok(LineReader::new(reader))
+    }
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        LineReader {
+            reader,
+            line: Vec::new(),
+            line_number: 0,
+            bytes_read: 0,
+            ended: false,
+        }
+    }
+
+    // Whether the file looks like a binary rather than text, going by whether there is
+    // a NUL byte near the start. This is checked before any of it has been read.
+    pub fn is_binary(&mut self) -> io::Result<bool> {
+        let start = self.reader.fill_buf()?;
+        Ok(start[..start.len().min(BINARY_CHECK)].contains(&0))
+    }
+
+    // how much of the file has been read so far, line endings included,
+    // counted after decompressing
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    // The next line and its 1-based line number, without the line ending and
+    // at most `LINE_BYTES_MAX` long. `None` once the end of the file is reached.
+    pub fn next_line(&mut self) -> io::Result<Option<(usize, &[u8])>> {
+        self.line.clear();
+        let read = (&mut self.reader).take(LINE_BYTES_MAX as u64).read_until(b'\n', &mut self.line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        self.bytes_read += read as u64;
+        self.ended = self.line.last() == Some(&b'\n');
+        if self.ended {
+            self.line.pop();
+            if self.line.last() == Some(&b'\r') {
+                self.line.pop();
+            }
+        } else if read == LINE_BYTES_MAX {
+            self.skip_rest()?;
+        }
+        self.line_number += 1;
+        Ok(Some((self.line_number, &self.line)))
+    }
+
+    // Whether the last line read had a line ending. The last line of a file
+    // that is still being written might not have one yet.
+    pub fn line_ended(&self) -> bool {
+        self.ended
+    }
+
+    // read past the rest of a line that was too long, a chunk at a time
+    fn skip_rest(&mut self) -> io::Result<()> {
+        loop {
+            let chunk = match self.reader.fill_buf() {
+                Ok(chunk) => chunk,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let (used, ended) = match chunk.iter().position(|&byte| byte == b'\n') {
+                Some(at) => (at + 1, true),
+                None => (chunk.len(), chunk.is_empty()),
+            };
+            self.reader.consume(used);
+            self.bytes_read += used as u64;
+            if ended {
+                self.ended = used > 0;
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::env;
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use std::process;
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        let mut reader = LineReader::open(path).unwrap();
+        let mut lines = Vec::new();
+        while let Some((_, line)) = reader.next_line().unwrap() {
+            lines.push(String::from_utf8_lossy(line).to_string());
+        }
+        lines
+    }
+
+    #[test]
+    fn compressed_files_are_told_by_their_first_bytes() {
+        let dir = env::temp_dir().join(format!("multi-threaded_search-compressed-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let text = "first line\nsecond line\n";
+        // the names say nothing, as with rotated logs
+        let files = [
+            ("plain", text.as_bytes().to_vec()),
+            ("gzipped", gzip(text)),
+            ("zstd", zstd::bulk::compress(text.as_bytes(), 3).unwrap()),
+            // streamed, so the frame doesn't say how big it is
+            ("zstd-streamed", zstd::encode_all(text.as_bytes(), 3).unwrap()),
+            // two members joined together, only the last one's size is at the end
+            ("gzipped-twice", [gzip(text), gzip("third\n")].concat()),
+        ];
+        for (name, bytes) in &files {
+            fs::write(dir.join(name), bytes).unwrap();
+        }
+        let expected = [
+            ("plain", None, None),
+            ("gzipped", Some(Compression::Gzip), Some(text.len() as u64)),
+            ("zstd", Some(Compression::Zstd), Some(text.len() as u64)),
+            ("zstd-streamed", Some(Compression::Zstd), None),
+            ("gzipped-twice", Some(Compression::Gzip), Some(6)),
+        ];
+        for (name, compression, size) in expected {
+            let path = dir.join(name);
+            assert_eq!(Compression::of(&path), compression, "{}", name);
+            assert_eq!(compression.and_then(|compression| compression.uncompressed_size(&path)), size, "{}", name);
+        }
+        assert_eq!(lines(&dir.join("zstd")), ["first line", "second line"]);
+        assert_eq!(lines(&dir.join("gzipped-twice")), ["first line", "second line", "third"]);
+        assert_eq!(Compression::of(&dir.join("missing")), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn overlong_lines_are_cut_short_and_read_past() {
+        let mut text = vec![b'a'; LINE_BYTES_MAX + 10];
+        text.extend_from_slice(b"\r\nnext\n");
+        text.extend(vec![b'b'; LINE_BYTES_MAX * 2]);
+        let mut lines = LineReader::new(BufReader::with_capacity(100, Cursor::new(text.clone())));
+        let (number, line) = lines.next_line().unwrap().unwrap();
+        assert_eq!((number, line.len()), (1, LINE_BYTES_MAX));
+        assert!(lines.line_ended());
+        assert_eq!(lines.next_line().unwrap(), Some((2, &b"next"[..])));
+        // still being written, so without a line ending
+        assert_eq!(lines.next_line().unwrap().map(|(number, line)| (number, line.len())), Some((3, LINE_BYTES_MAX)));
+        assert!(!lines.line_ended());
+        assert_eq!(lines.next_line().unwrap(), None);
+        assert_eq!(lines.bytes_read(), text.len() as u64);
+    }
+}
@@ -0,0 +1,86 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::Log;
+
+// The order results are listed in. Results are put in their place as they are
+// found, so it doesn't depend on which search thread got to a file first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sort {
+    #[default]
+    Name,
+    Path,
+    // newest first
+    Modified,
+    // biggest first
+    Size,
+    // the most hits first
+    Matches,
+    // the files with the most of the different terms first, then the most hits for their size
+    Relevance,
+}
+
+impl Sort {
+    pub const ALL: [Sort; 6] = [Sort::Name, Sort::Path, Sort::Modified, Sort::Size, Sort::Matches, Sort::Relevance];
+
+    pub fn compare(self, a: &Log, b: &Log) -> Ordering {
+        let order = match self {
+            Sort::Name => a.file.relative.file_name().cmp(&b.file.relative.file_name()),
+            Sort::Path => Ordering::Equal,
+            Sort::Modified => b.modified.cmp(&a.modified),
+            Sort::Size => b.size.cmp(&a.size),
+            Sort::Matches => total(b).cmp(&total(a)),
+            Sort::Relevance => terms_found(b)
+                .cmp(&terms_found(a))
+                // hits per byte, multiplied out so it stays in whole numbers
+                .then_with(|| (total(b) as u128 * a.size.max(1) as u128).cmp(&(total(a) as u128 * b.size.max(1) as u128))),
+        };
+        // the same files always come out in the same order
+        order.then_with(|| a.file.path().cmp(&b.file.path()))
+    }
+}
+
+fn total(log: &Log) -> usize {
+    log.hits.total()
+}
+
+fn terms_found(log: &Log) -> usize {
+    log.hits.counts().iter().filter(|hit| hit.count > 0).count()
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Sort::Name => "Name",
+            Sort::Path => "Path",
+            Sort::Modified => "Last modified",
+            Sort::Size => "Size",
+            Sort::Matches => "Matches",
+            Sort::Relevance => "Relevance",
+        })
+    }
+}
+
+// The results grouped by directory, as the positions in `logs` of the results in each.
+// The directories are in the order of their first result.
+pub fn groups(logs: &[Log]) -> Vec<(PathBuf, Vec<usize>)> {
+    let mut groups: Vec<(PathBuf, Vec<usize>)> = Vec::new();
+    for (i, log) in logs.iter().enumerate() {
+        let dir = group_of(log);
+        match groups.iter_mut().find(|(group, _)| *group == dir) {
+            Some((_, members)) => members.push(i),
+            None => groups.push((dir, vec![i])),
+        }
+    }
+    groups
+}
+
+// The directory a result is grouped under when grouping by directory,
+// with the root in front so the same directory under two roots stays apart.
+fn group_of(log: &Log) -> PathBuf {
+    match log.file.relative.parent() {
+        Some(dir) => log.file.root.join(dir),
+        None => log.file.root.clone(),
+    }
+}
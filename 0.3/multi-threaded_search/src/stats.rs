@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use crate::scan::FileError;
+
+// What searching one file took, measured by the worker that searched it.
+#[derive(Debug, Clone)]
+pub struct FileStats {
+    // how much of the file was read, files that can't match are given up on early
+    pub bytes: u64,
+    pub time: Duration,
+    // why the file couldn't be searched, if it couldn't
+    pub error: Option<FileError>,
+}
+
+// The numbers for one search, added to as the workers send back their batches.
+// Timing uses `Instant` so it can't be thrown off by the clock changing.
+#[derive(Debug, Clone)]
+pub struct SearchStats {
+    start: Instant,
+    // set once the search is done, until then the time so far is shown
+    time: Option<Duration>,
+    files: usize,
+    bytes: u64,
+    errors: usize,
+    // files skipped on purpose as they aren't text
+    binary: usize,
+    latencies: Vec<Duration>,
+    // files that were never searched as there were already enough results
+    skipped: usize,
+}
+
+// a search that has only just started
+impl Default for SearchStats {
+    fn default() -> Self {
+        SearchStats::new()
+    }
+}
+
+impl SearchStats {
+    pub fn new() -> Self {
+        SearchStats {
+            start: Instant::now(),
+            time: None,
+            files: 0,
+            bytes: 0,
+            errors: 0,
+            binary: 0,
+            latencies: Vec::new(),
+            skipped: 0,
+        }
+    }
+
+    pub fn add(&mut self, file: &FileStats) {
+        self.files += 1;
+        self.bytes += file.bytes;
+        self.latencies.push(file.time);
+        match &file.error {
+            Some(error) if error.is_deliberate() => self.binary += 1,
+            Some(_) => self.errors += 1,
+            None => (),
+        }
+    }
+
+    // the search is done, `skipped` files were left when it stopped
+    pub fn finish(&mut self, skipped: usize) {
+        self.time = Some(self.start.elapsed());
+        self.skipped = skipped;
+        self.latencies.sort();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.time.is_some()
+    }
+
+    // Each number with what it is, for the stats panel.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let time = self.time.unwrap_or_else(|| self.start.elapsed());
+        let secs = time.as_secs_f64().max(f64::EPSILON);
+        let mut fields = vec![
+            ("Time", format!("{:.1}ms", time.as_secs_f64() * 1000.0)),
+            ("Files", self.files.to_string()),
+            ("Read", format!("{:.1}MB", self.bytes as f64 / 1_000_000.0)),
+            ("Speed", format!("{:.1}MB/s", self.bytes as f64 / 1_000_000.0 / secs)),
+            ("Files/s", format!("{:.0}", self.files as f64 / secs)),
+            ("Errors", self.errors.to_string()),
+        ];
+        if self.binary > 0 {
+            fields.push(("Binary", self.binary.to_string()));
+        }
+        // the latencies are only sorted once the search is done
+        if self.is_finished() {
+            fields.push(("p50", self.percentile(50)));
+            fields.push(("p99", self.percentile(99)));
+            if self.skipped > 0 {
+                fields.push(("Not searched", self.skipped.to_string()));
+            }
+        }
+        fields
+    }
+
+    // the per file latency that `p` percent of files were searched within
+    fn percentile(&self, p: usize) -> String {
+        if self.latencies.is_empty() {
+            return "-".to_string();
+        }
+        // nearest rank, so p99 of a handful of files is the slowest one
+        let rank = (p * self.latencies.len()).div_ceil(100);
+        let latency = self.latencies[rank.max(1) - 1];
+        format!("{:.2}ms", latency.as_secs_f64() * 1000.0)
+    }
+}
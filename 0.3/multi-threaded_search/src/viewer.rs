@@ -0,0 +1,381 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use iced::{button, slider, Button, Checkbox, Column, Command, Element, Length, Row, Slider, Text};
+
+use multi_threaded_search::highlight::Snippet;
+use multi_threaded_search::line_index::{Followed, LineIndex};
+use multi_threaded_search::query::Query;
+use multi_threaded_search::scan::FileError;
+
+use crate::{lines_height, lines_view, ERROR_COLOR, LINE_NUMBER_COLOR};
+
+// how many lines of an opened file are shown at once
+const WINDOW: usize = 40;
+// a match jumped to is shown this many lines down, so there is some context above it
+const CONTEXT: usize = 5;
+
+// An opened `Log`. Only the lines in view are read and kept, the rest of the file
+// is found again through its `LineIndex` when it is moved to.
+#[derive(Debug, Clone)]
+pub struct Viewer {
+    path: PathBuf,
+    query: Option<Arc<Query>>,
+    // `None` while the file is first being read through
+    index: Option<Arc<LineIndex>>,
+    error: Option<FileError>,
+    // the line at the top of the window, counting from 1
+    first: usize,
+    lines: Vec<Snippet>,
+    // whether some lines are being read, only one read is started at a time so
+    // dragging the slider doesn't start a read for every line it passes
+    reading: bool,
+    // the match that was last jumped to
+    current: Option<usize>,
+    // keeping up with lines being added to the file, see `LineIndex::follow`
+    following: bool,
+    // whether it is being checked for new lines, and whether it changed again since
+    checking: bool,
+    changed: bool,
+    // what happened to the file while following it, if anything out of the ordinary
+    note: Option<&'static str>,
+    top_button: button::State,
+    up_button: button::State,
+    down_button: button::State,
+    bottom_button: button::State,
+    previous_match_button: button::State,
+    next_match_button: button::State,
+    slider: slider::State,
+}
+
+#[derive(Debug, Clone)]
+pub enum ViewerMessage {
+    Indexed(PathBuf, Result<Arc<LineIndex>, FileError>),
+    Read(Arc<LineIndex>, usize, Result<Vec<Snippet>, FileError>),
+    GoTo(usize),
+    PreviousMatch,
+    NextMatch,
+    Follow(bool),
+    // the file has been written to, removed or replaced, sent while following
+    FileChanged,
+    Followed(Arc<LineIndex>, Result<Followed, FileError>),
+}
+
+// shown while following a file that has been removed, e.g. while it is rotated
+const GONE: &str = "The file is gone, waiting for it to come back";
+
+impl Viewer {
+    pub fn open(path: PathBuf, query: Option<Arc<Query>>) -> (Viewer, Command<ViewerMessage>) {
+        let command = {
+            let (path, query) = (path.clone(), query.clone());
+            Command::perform(
+                async move {
+                    let index = LineIndex::build(&path, query.as_deref()).map(Arc::new);
+                    (path, index)
+                },
+                |(path, index)| ViewerMessage::Indexed(path, index),
+            )
+        };
+        let viewer = Viewer {
+            path,
+            query,
+            index: None,
+            error: None,
+            first: 1,
+            lines: Vec::new(),
+            reading: false,
+            current: None,
+            following: false,
+            checking: false,
+            changed: false,
+            note: None,
+            top_button: button::State::new(),
+            up_button: button::State::new(),
+            down_button: button::State::new(),
+            bottom_button: button::State::new(),
+            previous_match_button: button::State::new(),
+            next_match_button: button::State::new(),
+            slider: slider::State::new(),
+        };
+        (viewer, command)
+    }
+
+    // the whole file has been read through once it is indexed
+    pub fn index(&self) -> Option<&LineIndex> {
+        self.index.as_deref()
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.following
+    }
+
+    pub fn update(&mut self, message: ViewerMessage) -> Command<ViewerMessage> {
+        match message {
+            // from a viewer that has since been closed, maybe of another file
+            ViewerMessage::Indexed(path, _) if path != self.path || self.index.is_some() => Command::none(),
+            ViewerMessage::Indexed(_, Err(error)) => {
+                self.error = Some(error);
+                Command::none()
+            }
+            ViewerMessage::Indexed(_, Ok(index)) => {
+                // start at the first match, as that is most likely what it was opened for
+                let first = match index.next_match(0) {
+                    Some(line) => {
+                        self.current = Some(line);
+                        line.saturating_sub(CONTEXT)
+                    }
+                    None => 1,
+                };
+                self.index = Some(index);
+                self.go_to(first)
+            }
+            ViewerMessage::Read(index, first, result) => {
+                self.reading = false;
+                if first != self.first || !self.is_current(&index) {
+                    // moved on or the file changed while this was being read
+                    return self.read();
+                }
+                match result {
+                    Ok(lines) => self.lines = lines,
+                    Err(FileError::NotFound) if self.following => self.note = Some(GONE),
+                    Err(error) => self.error = Some(error),
+                }
+                Command::none()
+            }
+            ViewerMessage::GoTo(first) => self.go_to(first),
+            ViewerMessage::PreviousMatch => match self.previous_match() {
+                Some(line) => self.jump_to(line),
+                None => Command::none(),
+            },
+            ViewerMessage::NextMatch => match self.next_match() {
+                Some(line) => self.jump_to(line),
+                None => Command::none(),
+            },
+            ViewerMessage::Follow(following) => {
+                self.following = following;
+                self.note = None;
+                if !following {
+                    return Command::none();
+                }
+                // catch up with whatever was added since it was opened, then keep up from there
+                Command::batch(vec![self.go_to(usize::MAX), self.check()])
+            }
+            ViewerMessage::FileChanged if self.following => self.check(),
+            ViewerMessage::FileChanged => Command::none(),
+            ViewerMessage::Followed(index, result) => {
+                if !self.is_current(&index) {
+                    return Command::none();
+                }
+                self.checking = false;
+                // it only scrolls along with the file if it was already at the end
+                let at_end = self.first >= self.last_first();
+                let command = match result {
+                    Ok(Followed::Unchanged) => {
+                        self.note = None;
+                        Command::none()
+                    }
+                    Ok(Followed::Appended(index)) => {
+                        self.note = None;
+                        self.index = Some(Arc::new(index));
+                        if at_end {
+                            self.go_to(usize::MAX)
+                        } else {
+                            Command::none()
+                        }
+                    }
+                    Ok(Followed::Replaced(index)) => {
+                        self.note = Some("The file was truncated or replaced, showing the new one");
+                        // the line numbers are of other lines now
+                        self.current = None;
+                        self.index = Some(Arc::new(index));
+                        self.go_to(if at_end { usize::MAX } else { self.first })
+                    }
+                    Err(FileError::NotFound) => {
+                        self.note = Some(GONE);
+                        Command::none()
+                    }
+                    Err(error) => {
+                        self.error = Some(error);
+                        Command::none()
+                    }
+                };
+                if self.changed && self.following {
+                    self.changed = false;
+                    Command::batch(vec![command, self.check()])
+                } else {
+                    command
+                }
+            }
+        }
+    }
+
+    fn is_current(&self, index: &Arc<LineIndex>) -> bool {
+        self.index.as_ref().is_some_and(|current| Arc::ptr_eq(current, index))
+    }
+
+    // Look for new lines, unless that's already being done. Until the file has been
+    // opened there is nothing to do, it is read up to the end anyway.
+    fn check(&mut self) -> Command<ViewerMessage> {
+        let index = match &self.index {
+            Some(index) => index.clone(),
+            None => return Command::none(),
+        };
+        if self.checking {
+            self.changed = true;
+            return Command::none();
+        }
+        self.checking = true;
+        let query = self.query.clone();
+        Command::perform(
+            async move {
+                let followed = index.follow(query.as_deref());
+                (index, followed)
+            },
+            |(index, followed)| ViewerMessage::Followed(index, followed),
+        )
+    }
+
+    // The match last jumped to, if it is still in view. Once the window has been
+    // moved away from it the matches before and after are those around the window.
+    fn current(&self) -> Option<usize> {
+        self.current.filter(|&line| line >= self.first && line < self.first + WINDOW)
+    }
+
+    fn previous_match(&self) -> Option<usize> {
+        let from = self.current().unwrap_or(self.first);
+        self.index.as_ref()?.previous_match(from)
+    }
+
+    fn next_match(&self) -> Option<usize> {
+        let from = self.current().unwrap_or(self.first - 1);
+        self.index.as_ref()?.next_match(from)
+    }
+
+    fn jump_to(&mut self, line: usize) -> Command<ViewerMessage> {
+        self.current = Some(line);
+        self.go_to(line.saturating_sub(CONTEXT))
+    }
+
+    fn go_to(&mut self, first: usize) -> Command<ViewerMessage> {
+        self.first = first.clamp(1, self.last_first());
+        if self.reading {
+            // picked up once the read going on now is done
+            return Command::none();
+        }
+        self.read()
+    }
+
+    fn read(&mut self) -> Command<ViewerMessage> {
+        let index = match &self.index {
+            Some(index) => index.clone(),
+            None => return Command::none(),
+        };
+        let (first, query) = (self.first, self.query.clone());
+        self.reading = true;
+        Command::perform(
+            async move {
+                let lines = index.read(first, WINDOW, query.as_deref());
+                (index, first, lines)
+            },
+            |(index, first, lines)| ViewerMessage::Read(index, first, lines),
+        )
+    }
+
+    // the lowest the window can go while still being full
+    fn last_first(&self) -> usize {
+        self.index
+            .as_ref()
+            .map_or(1, |index| index.lines.saturating_sub(WINDOW - 1).max(1))
+    }
+
+    // Roughly the height of `view` with the padding `Log` puts around it,
+    // the controls, the follow checkbox and the slider take up about 140.
+    pub fn height(&self) -> f32 {
+        140.0 + lines_height(&self.lines)
+    }
+
+    pub fn view(&mut self) -> Element<'_, ViewerMessage> {
+        if let Some(error) = &self.error {
+            return Text::new(format!("Unable to show this file: {}", error))
+                .size(16)
+                .color(ERROR_COLOR)
+                .into();
+        }
+        let index = match &self.index {
+            Some(index) => index,
+            None => return Text::new("Opening . . .").size(16).into(),
+        };
+        let last_first = self.last_first();
+        let button = |state, label, message: Option<ViewerMessage>| {
+            let button = Button::new(state, Text::new(label).size(16)).padding(5);
+            match message {
+                Some(message) => button.on_press(message),
+                None => button,
+            }
+        };
+        let up = self.first > 1;
+        let down = self.first < last_first;
+        let previous_match = self.previous_match();
+        let next_match = self.next_match();
+        let shown = if index.lines == 0 {
+            "Empty file".to_string()
+        } else {
+            format!(
+                "Lines {} to {} of {}",
+                self.first,
+                (self.first + WINDOW - 1).min(index.lines),
+                index.lines
+            )
+        };
+        let matches = match self.current().and_then(|line| index.match_number(line)) {
+            _ if index.matches.is_empty() => String::new(),
+            Some(number) => format!("Match {} of {}", number, index.matches.len()),
+            None => format!("{} matching lines", index.matches.len()),
+        };
+        let controls = Row::new()
+            .spacing(10)
+            .push(button(&mut self.top_button, "Top", Some(ViewerMessage::GoTo(1)).filter(|_| up)))
+            .push(button(
+                &mut self.up_button,
+                "Up",
+                Some(ViewerMessage::GoTo(self.first.saturating_sub(WINDOW))).filter(|_| up),
+            ))
+            .push(button(
+                &mut self.down_button,
+                "Down",
+                Some(ViewerMessage::GoTo(self.first + WINDOW)).filter(|_| down),
+            ))
+            .push(button(
+                &mut self.bottom_button,
+                "Bottom",
+                Some(ViewerMessage::GoTo(last_first)).filter(|_| down),
+            ))
+            .push(Text::new(shown).size(16).color(LINE_NUMBER_COLOR).width(Length::Fill))
+            .push(Text::new(matches).size(16).color(LINE_NUMBER_COLOR))
+            .push(button(
+                &mut self.previous_match_button,
+                "Previous match",
+                previous_match.map(|_| ViewerMessage::PreviousMatch),
+            ))
+            .push(button(
+                &mut self.next_match_button,
+                "Next match",
+                next_match.map(|_| ViewerMessage::NextMatch),
+            ));
+        let mut follow = Row::new()
+            .spacing(20)
+            .push(Checkbox::new(self.following, "Follow", ViewerMessage::Follow).size(16).text_size(16));
+        if let Some(note) = self.note {
+            follow = follow.push(Text::new(note).size(16).color(LINE_NUMBER_COLOR));
+        }
+        let mut column = Column::new().spacing(10).push(controls).push(follow);
+        if last_first > 1 {
+            column = column.push(Slider::new(
+                &mut self.slider,
+                1..=last_first as u32,
+                self.first as u32,
+                |first| ViewerMessage::GoTo(first as usize),
+            ));
+        }
+        column.push(lines_view(&self.lines)).into()
+    }
+}
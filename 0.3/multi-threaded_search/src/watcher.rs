@@ -0,0 +1,136 @@
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use iced::futures::{self, channel::mpsc, stream::BoxStream, StreamExt};
+use iced_native::subscription::Recipe;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+// A file or directory under one of the watched roots that was created,
+// written to or deleted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileChange {
+    Changed(PathBuf),
+    Removed(PathBuf),
+}
+
+impl FileChange {
+    pub fn path(&self) -> &Path {
+        match self {
+            FileChange::Changed(path) | FileChange::Removed(path) => path,
+        }
+    }
+}
+
+// Watch `roots` and everything below them for changes (inotify on Linux),
+// for as long as the subscription is active.
+pub fn watch(roots: Vec<PathBuf>) -> iced::Subscription<Vec<FileChange>> {
+    iced::Subscription::from_recipe(DirWatcher { roots })
+}
+
+struct DirWatcher {
+    roots: Vec<PathBuf>,
+}
+
+impl<H, I> Recipe<H, I> for DirWatcher
+where
+    H: Hasher,
+{
+    type Output = Vec<FileChange>;
+
+    fn hash(&self, state: &mut H) {
+        std::any::TypeId::of::<Self>().hash(state);
+        self.roots.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _input: BoxStream<'static, I>) -> BoxStream<'static, Self::Output> {
+        // notify calls us back on its own thread, hand the events over to the stream
+        let (sender, receiver) = mpsc::unbounded();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.unbounded_send(event);
+        });
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(_) => return Box::pin(futures::stream::empty()),
+        };
+        let roots = self.roots;
+        let mut waiting = roots.clone();
+        // the files already in the roots that are there now are searched anyway
+        watch_roots(&mut watcher, &mut waiting, &mut Vec::new());
+        Box::pin(futures::stream::unfold(
+            (watcher, receiver, roots, waiting),
+            |(mut watcher, mut receiver, roots, mut waiting): (RecommendedWatcher, _, Vec<PathBuf>, _)| async move {
+                loop {
+                    let mut changes = Vec::new();
+                    collect(receiver.next().await?, &mut changes);
+                    // writing a file fires a burst of events, send them as one batch
+                    while let Ok(event) = receiver.try_recv() {
+                        collect(event, &mut changes);
+                    }
+                    // a root that is deleted takes its watch with it, wait for it to come back
+                    for root in &roots {
+                        let removed = changes
+                            .iter()
+                            .any(|change| matches!(change, FileChange::Removed(path) if root.starts_with(path)));
+                        if removed && !waiting.contains(root) {
+                            waiting.push(root.clone());
+                        }
+                    }
+                    if !waiting.is_empty() {
+                        watch_roots(&mut watcher, &mut waiting, &mut changes);
+                    }
+                    // the directories above a missing root are watched too, but what
+                    // happens in them is none of our business
+                    changes.retain(|change| roots.iter().any(|root| change.path().starts_with(root)));
+                    if !changes.is_empty() {
+                        return Some((changes, (watcher, receiver, roots, waiting)));
+                    }
+                }
+            },
+        ))
+    }
+}
+
+// Start watching the `waiting` roots that exist now, adding them to `changes` as the
+// files already in them haven't been searched. Those still missing are waited for by
+// watching the closest directory above them that does exist, so any event there is a
+// chance to look again. Those directories are never unwatched, one of them might be
+// under another root.
+fn watch_roots(watcher: &mut RecommendedWatcher, waiting: &mut Vec<PathBuf>, changes: &mut Vec<FileChange>) {
+    waiting.retain(|root| {
+        let watched = watcher.watch(root, RecursiveMode::Recursive).is_ok() || {
+            if let Some(parent) = root.ancestors().skip(1).find(|dir| dir.is_dir()) {
+                let _ = watcher.watch(parent, RecursiveMode::NonRecursive);
+            }
+            // it may have been created before that watch started, then there is no event to wait for
+            watcher.watch(root, RecursiveMode::Recursive).is_ok()
+        };
+        let change = FileChange::Changed(root.clone());
+        if watched && !changes.contains(&change) {
+            changes.push(change);
+        }
+        !watched
+    });
+}
+
+fn collect(event: notify::Result<Event>, changes: &mut Vec<FileChange>) {
+    let event = match event {
+        Ok(event) => event,
+        Err(_) => return,
+    };
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => (),
+        // reading files (including our own searches) also fires events
+        _ => return,
+    }
+    for path in event.paths {
+        // renames report both paths, so look at what is on disk now
+        // rather than trusting the event kind
+        let change = if path.exists() {
+            FileChange::Changed(path)
+        } else {
+            FileChange::Removed(path)
+        };
+        if !changes.contains(&change) {
+            changes.push(change);
+        }
+    }
+}